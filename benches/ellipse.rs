@@ -1,8 +1,23 @@
 use std::time::Duration;
 
-use criterion::{criterion_group, criterion_main, Bencher, Criterion};
-use rust_kepler_solver::ellipse::EllipseSolver;
+use criterion::{criterion_group, criterion_main, measurement::WallTime, Bencher, BenchmarkGroup, Criterion};
+use rust_kepler_solver::{config::{Precision, SolverConfig}, ellipse::EllipseSolver, method::{Danby, Enrke, Halley, KeplerMethod, Laguerre, Markley, Mikkola, Newton}};
 
+fn bench_method<M: KeplerMethod<f64> + Copy>(group: &mut BenchmarkGroup<WallTime>, name: &str, method: M, eccentricities: &[f64], mean_anomalies: &[f64]) {
+    for e in eccentricities {
+        let solver = EllipseSolver::with_method(*e, method, SolverConfig::ellipse(Precision::Balanced));
+        group.throughput(criterion::Throughput::Elements(mean_anomalies.len() as u64));
+        group.bench_function(format!("{}/{}", name, e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                for m in mean_anomalies {
+                    solver.solve(*m);
+                };
+            });
+        });
+    }
+}
+
+#[allow(clippy::approx_constant)] // 6.283 is deliberately just below 2pi
 pub fn bench(c: &mut Criterion) {
     let eccentricities = [0.01, 0.1, 0.3, 0.5, 0.7, 0.9, 0.99];
     let mean_anomalies = [0.01, 0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 5.5, 6.0, 6.283];
@@ -23,6 +38,88 @@ pub fn bench(c: &mut Criterion) {
             });
         });
     }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("ellipse_batch");
+
+    group.warm_up_time(Duration::from_millis(1000));
+    group.measurement_time(Duration::from_millis(2000));
+
+    // Same inputs as above, repeated so the batch is a realistic size
+    let batch: Vec<f64> = mean_anomalies.iter().cycle().take(mean_anomalies.len() * 1000).copied().collect();
+    let mut out = vec![0.0; batch.len()];
+    for e in eccentricities {
+        let solver = EllipseSolver::new(e);
+        group.throughput(criterion::Throughput::Elements(batch.len() as u64));
+        group.bench_function(format!("{}", e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                solver.solve_into(&batch, &mut out);
+            });
+        });
+    }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("ellipse_x4");
+
+    group.warm_up_time(Duration::from_millis(1000));
+    group.measurement_time(Duration::from_millis(2000));
+
+    // Runs the vector kernel with --features simd, and the scalar fallback without
+    for e in eccentricities {
+        let solver = EllipseSolver::new(e);
+        group.throughput(criterion::Throughput::Elements(batch.len() as u64));
+        group.bench_function(format!("{}", e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                for (mean_anomalies, out) in batch.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+                    out.copy_from_slice(&solver.solve_x4(mean_anomalies.try_into().unwrap()));
+                }
+            });
+        });
+    }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("ellipse_methods");
+
+    group.warm_up_time(Duration::from_millis(1000));
+    group.measurement_time(Duration::from_millis(2000));
+
+    // Every method on the same inputs as the first group, so they can be compared directly
+    bench_method(&mut group, "laguerre", Laguerre, &eccentricities, &mean_anomalies);
+    bench_method(&mut group, "newton", Newton, &eccentricities, &mean_anomalies);
+    bench_method(&mut group, "halley", Halley, &eccentricities, &mean_anomalies);
+    bench_method(&mut group, "danby", Danby, &eccentricities, &mean_anomalies);
+    bench_method(&mut group, "mikkola", Mikkola, &eccentricities, &mean_anomalies);
+    bench_method(&mut group, "markley", Markley, &eccentricities, &mean_anomalies);
+    bench_method(&mut group, "enrke", Enrke::new(1.0e-10), &eccentricities, &mean_anomalies);
+
+    // The contour and table depend on the eccentricity, so these can't go through bench_method
+    for e in eccentricities {
+        let solver = EllipseSolver::contour(e, SolverConfig::ellipse(Precision::Balanced));
+        group.throughput(criterion::Throughput::Elements(mean_anomalies.len() as u64));
+        group.bench_function(format!("contour/{}", e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                for m in &mean_anomalies {
+                    solver.solve(*m);
+                };
+            });
+        });
+    }
+    for e in eccentricities {
+        let solver = EllipseSolver::piecewise(e, SolverConfig::ellipse(Precision::Balanced));
+        group.throughput(criterion::Throughput::Elements(mean_anomalies.len() as u64));
+        group.bench_function(format!("piecewise/{}", e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                for m in &mean_anomalies {
+                    solver.solve(*m);
+                };
+            });
+        });
+    }
+
+    group.finish();
 }
 
 criterion_group!(benches, bench);
@@ -23,6 +23,49 @@ pub fn bench(c: &mut Criterion) {
             });
         });
     }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("hyperbola_batch");
+
+    group.warm_up_time(Duration::from_millis(1000));
+    group.measurement_time(Duration::from_millis(2000));
+
+    // Same inputs as above, repeated so the batch is a realistic size
+    let batch: Vec<f64> = mean_anomalies.iter().cycle().take(mean_anomalies.len() * 1000).copied().collect();
+    let mut out = vec![0.0; batch.len()];
+    for e in eccentricities {
+        let solver = HyperbolaSolver::new(e);
+        group.throughput(criterion::Throughput::Elements(batch.len() as u64));
+        group.bench_function(format!("{}", e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                solver.solve_into(&batch, &mut out);
+            });
+        });
+    }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("hyperbola_x4");
+
+    group.warm_up_time(Duration::from_millis(1000));
+    group.measurement_time(Duration::from_millis(2000));
+
+    // Runs the vector kernel with --features simd, and the scalar fallback without. The kernel only
+    // has a fast path for hyperbolic eccentricities, so it gets its own
+    for e in [1.01, 1.1, 1.3, 1.5, 2.0, 5.0, 20.0] {
+        let solver = HyperbolaSolver::new(e);
+        group.throughput(criterion::Throughput::Elements(batch.len() as u64));
+        group.bench_function(format!("{}", e).as_str(), |b: &mut Bencher| {
+            b.iter(|| {
+                for (mean_anomalies, out) in batch.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+                    out.copy_from_slice(&solver.solve_x4(mean_anomalies.try_into().unwrap()));
+                }
+            });
+        });
+    }
+
+    group.finish();
 }
 
 criterion_group!(benches, bench);
@@ -0,0 +1,244 @@
+//! Conversions between the mean, eccentric, hyperbolic, parabolic and true anomalies. Conversions
+//! that only evaluate a formula return the result directly, and conversions that run a solver or
+//! can fall outside the orbit return a `Result`.
+//!
+//! The elliptic conversions between E and the true anomaly preserve the number of revolutions, so
+//! they are continuous and work for any real input. The hyperbolic and parabolic conversions accept
+//! true anomalies in any revolution, but return them in (-pi, pi).
+
+use crate::{ellipse::EllipseSolver, error::SolveError, float::Float, hyperbola::HyperbolaSolver, kepler::{Anomaly, KeplerSolver, DEFAULT_PARABOLIC_TOLERANCE}, parabola::ParabolaSolver};
+
+/// Kepler's equation, M = E - e sin(E)
+pub fn eccentric_to_mean<T: Float>(eccentricity: T, eccentric_anomaly: T) -> T {
+    eccentric_anomaly - eccentricity * eccentric_anomaly.sin()
+}
+
+/// Returns the eccentric anomaly in [0, 2pi)
+pub fn mean_to_eccentric<T: Float>(eccentricity: T, mean_anomaly: T) -> Result<T, SolveError> {
+    EllipseSolver::new(eccentricity).try_solve(mean_anomaly)
+}
+
+/// Works for 0 <= eccentricity < 1 and any eccentric anomaly, returning a true anomaly in the same
+/// revolution
+pub fn eccentric_to_true<T: Float>(eccentricity: T, eccentric_anomaly: T) -> T {
+    // The half-angle formula tan(v/2) = sqrt((1+e)/(1-e)) tan(E/2) needs its quadrant fixing up and
+    // blows up at E = pi, so use the difference form instead, which is smooth everywhere
+    let beta = elliptic_beta(eccentricity);
+    let (sin, cos) = (eccentric_anomaly.sin(), eccentric_anomaly.cos());
+    eccentric_anomaly + T::from_f64(2.0) * T::atan2(beta * sin, T::from_f64(1.0) - beta * cos)
+}
+
+/// Works for 0 <= eccentricity < 1 and any true anomaly, returning an eccentric anomaly in the same
+/// revolution
+pub fn true_to_eccentric<T: Float>(eccentricity: T, true_anomaly: T) -> T {
+    let beta = elliptic_beta(eccentricity);
+    let (sin, cos) = (true_anomaly.sin(), true_anomaly.cos());
+    true_anomaly - T::from_f64(2.0) * T::atan2(beta * sin, T::from_f64(1.0) + beta * cos)
+}
+
+/// beta = e / (1 + sqrt(1 - e^2)), written so that it doesn't cancel for small e
+fn elliptic_beta<T: Float>(eccentricity: T) -> T {
+    let one = T::from_f64(1.0);
+    eccentricity / (one + T::sqrt((one - eccentricity) * (one + eccentricity)))
+}
+
+/// The hyperbolic Kepler equation, M = e sinh(F) - F
+pub fn hyperbolic_to_mean<T: Float>(eccentricity: T, hyperbolic_anomaly: T) -> T {
+    eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly
+}
+
+pub fn mean_to_hyperbolic<T: Float>(eccentricity: T, mean_anomaly: T) -> Result<T, SolveError> {
+    HyperbolaSolver::new(eccentricity).try_solve(mean_anomaly)
+}
+
+/// Works for eccentricity > 1, returns the true anomaly in (-acos(-1/e), acos(-1/e))
+pub fn hyperbolic_to_true<T: Float>(eccentricity: T, hyperbolic_anomaly: T) -> T {
+    let one = T::from_f64(1.0);
+    let half = hyperbolic_anomaly / T::from_f64(2.0);
+    T::from_f64(2.0) * T::atan2(T::sqrt(eccentricity + one) * half.sinh(), T::sqrt(eccentricity - one) * half.cosh())
+}
+
+/// Works for eccentricity > 1, returns an error if the true anomaly is on or beyond the asymptote
+pub fn true_to_hyperbolic<T: Float>(eccentricity: T, true_anomaly: T) -> Result<T, SolveError> {
+    let one = T::from_f64(1.0);
+    if !true_anomaly.is_finite() || !eccentricity.is_finite() {
+        return Err(SolveError::NonFiniteInput);
+    }
+    let denominator = one + eccentricity * true_anomaly.cos();
+    if denominator <= T::from_f64(0.0) {
+        return Err(beyond_asymptote(eccentricity, true_anomaly));
+    }
+    // sinh(F) = sqrt(e^2 - 1) sin(v) / (1 + e cos(v)), which has the right sign everywhere, unlike
+    // the half-angle form that goes through atanh
+    let sqrt_e2_minus_1 = T::sqrt((eccentricity - one) * (eccentricity + one));
+    Ok(T::asinh(sqrt_e2_minus_1 * true_anomaly.sin() / denominator))
+}
+
+/// Barker's equation, M = D + D^3 / 3
+pub fn parabolic_to_mean<T: Float>(parabolic_anomaly: T) -> T {
+    parabolic_anomaly + parabolic_anomaly.powi(3) / T::from_f64(3.0)
+}
+
+pub fn mean_to_parabolic<T: Float>(mean_anomaly: T) -> Result<T, SolveError> {
+    ParabolaSolver::new().try_solve(mean_anomaly)
+}
+
+/// Returns the true anomaly in (-pi, pi)
+pub fn parabolic_to_true<T: Float>(parabolic_anomaly: T) -> T {
+    T::from_f64(2.0) * parabolic_anomaly.atan()
+}
+
+/// Returns an error if the true anomaly is pi, which is at infinity on a parabola
+pub fn true_to_parabolic<T: Float>(true_anomaly: T) -> Result<T, SolveError> {
+    // tan(v/2) = sin(v) / (1 + cos(v)), which doesn't care which revolution v is in
+    if !true_anomaly.is_finite() {
+        return Err(SolveError::NonFiniteInput);
+    }
+    let denominator = T::from_f64(1.0) + true_anomaly.cos();
+    if denominator <= T::from_f64(0.0) {
+        return Err(beyond_asymptote(T::from_f64(1.0), true_anomaly));
+    }
+    Ok(true_anomaly.sin() / denominator)
+}
+
+fn beyond_asymptote<T: Float>(eccentricity: T, true_anomaly: T) -> SolveError {
+    SolveError::BeyondAsymptote { eccentricity: eccentricity.to_f64(), true_anomaly: true_anomaly.to_f64() }
+}
+
+impl<T: Float> Anomaly<T> {
+    /// Converts to the true anomaly. `eccentricity` is ignored for parabolic anomalies
+    pub fn to_true(&self, eccentricity: T) -> T {
+        match *self {
+            Anomaly::Eccentric(eccentric_anomaly) => eccentric_to_true(eccentricity, eccentric_anomaly),
+            Anomaly::Parabolic(parabolic_anomaly) => parabolic_to_true(parabolic_anomaly),
+            Anomaly::Hyperbolic(hyperbolic_anomaly) => hyperbolic_to_true(eccentricity, hyperbolic_anomaly),
+        }
+    }
+
+    /// Converts a true anomaly to the anomaly `KeplerSolver::new(eccentricity)` would solve for
+    pub fn from_true(eccentricity: T, true_anomaly: T) -> Result<Self, SolveError> {
+        let one = T::from_f64(1.0);
+        if !true_anomaly.is_finite() || !eccentricity.is_finite() {
+            Err(SolveError::NonFiniteInput)
+        } else if (eccentricity - one).abs() <= T::from_f64(DEFAULT_PARABOLIC_TOLERANCE) {
+            true_to_parabolic(true_anomaly).map(Anomaly::Parabolic)
+        } else if eccentricity < T::from_f64(0.0) {
+            Err(SolveError::EccentricityOutOfRange { eccentricity: eccentricity.to_f64() })
+        } else if eccentricity < one {
+            Ok(Anomaly::Eccentric(true_to_eccentric(eccentricity, true_anomaly)))
+        } else {
+            true_to_hyperbolic(eccentricity, true_anomaly).map(Anomaly::Hyperbolic)
+        }
+    }
+
+    /// Converts to the mean anomaly in the convention of the solver for this kind of anomaly
+    pub fn to_mean(&self, eccentricity: T) -> T {
+        match *self {
+            Anomaly::Eccentric(eccentric_anomaly) => eccentric_to_mean(eccentricity, eccentric_anomaly),
+            Anomaly::Parabolic(parabolic_anomaly) => parabolic_to_mean(parabolic_anomaly),
+            Anomaly::Hyperbolic(hyperbolic_anomaly) => hyperbolic_to_mean(eccentricity, hyperbolic_anomaly),
+        }
+    }
+}
+
+/// Solves for the true anomaly with whichever solver `KeplerSolver::new` would pick, so the mean
+/// anomaly is in that solver's convention. Build a `KeplerSolver` and use `Anomaly::to_true` instead
+/// if you're converting many mean anomalies for the same orbit
+pub fn mean_to_true<T: Float>(eccentricity: T, mean_anomaly: T) -> Result<T, SolveError> {
+    KeplerSolver::new(eccentricity)
+        .try_solve(mean_anomaly)
+        .map(|anomaly| anomaly.to_true(eccentricity))
+}
+
+/// Inverse of `mean_to_true`
+pub fn true_to_mean<T: Float>(eccentricity: T, true_anomaly: T) -> Result<T, SolveError> {
+    Anomaly::from_true(eccentricity, true_anomaly).map(|anomaly| anomaly.to_mean(eccentricity))
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use crate::{error::SolveError, kepler::Anomaly};
+
+    use super::{eccentric_to_mean, eccentric_to_true, hyperbolic_to_mean, hyperbolic_to_true, mean_to_eccentric, mean_to_hyperbolic, mean_to_true, parabolic_to_true, true_to_eccentric, true_to_hyperbolic, true_to_mean, true_to_parabolic};
+
+    #[test]
+    fn test_anomaly_elliptic() {
+        for e in [0.0, 1.0e-9, 0.3, 0.9, 0.999_999] {
+            for x in -2000..2000 {
+                let eccentric_anomaly = x as f64 / 100.0;
+                let true_anomaly = eccentric_to_true(e, eccentric_anomaly);
+                // Compare against the textbook formulas, which only agree up to a multiple of 2pi
+                let expected = f64::atan2(f64::sqrt(1.0 - e*e) * eccentric_anomaly.sin(), eccentric_anomaly.cos() - e);
+                let difference = true_anomaly - expected;
+                assert!((difference - 2.0 * PI * (difference / (2.0 * PI)).round()).abs() < 1.0e-9, "{} {}", e, eccentric_anomaly);
+                // Same revolution, so the round trip is exact up to rounding
+                assert!((eccentric_anomaly - true_to_eccentric(e, true_anomaly)).abs() < 1.0e-12 * eccentric_anomaly.abs().max(1.0), "{} {}", e, eccentric_anomaly);
+                assert!((true_anomaly - eccentric_anomaly).abs() < PI);
+            }
+        }
+        let m = eccentric_to_mean(0.5, 2.0);
+        assert!((mean_to_eccentric(0.5, m).unwrap() - 2.0_f64).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn test_anomaly_hyperbolic() {
+        for e in [1.001, 1.1, 2.0, 100.0] {
+            for x in -1000..1000 {
+                let hyperbolic_anomaly = x as f64 / 100.0;
+                let true_anomaly = hyperbolic_to_true(e, hyperbolic_anomaly);
+                let expected = f64::atan2(f64::sqrt(e*e - 1.0) * hyperbolic_anomaly.sinh(), e - hyperbolic_anomaly.cosh());
+                assert!((true_anomaly - expected).abs() < 1.0e-9, "{} {}", e, hyperbolic_anomaly);
+                let round_trip = true_to_hyperbolic(e, true_anomaly).unwrap();
+                assert!((hyperbolic_anomaly - round_trip).abs() < 1.0e-7 * hyperbolic_anomaly.abs().max(1.0), "{} {}", e, hyperbolic_anomaly);
+                // Works in any revolution
+                assert!((true_to_hyperbolic(e, true_anomaly + 4.0 * PI).unwrap() - round_trip).abs() < 1.0e-7 * round_trip.abs().max(1.0));
+            }
+        }
+        let m = hyperbolic_to_mean(1.5, 2.0);
+        assert!((mean_to_hyperbolic(1.5, m).unwrap() - 2.0_f64).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn test_anomaly_beyond_asymptote() {
+        let asymptote = f64::acos(-1.0 / 2.0);
+        assert!(true_to_hyperbolic(2.0, asymptote - 1.0e-6).is_ok());
+        assert!(true_to_hyperbolic(2.0, -asymptote + 1.0e-6).is_ok());
+        assert_eq!(true_to_hyperbolic(2.0, asymptote + 1.0e-6), Err(SolveError::BeyondAsymptote { eccentricity: 2.0, true_anomaly: asymptote + 1.0e-6 }));
+        assert!(true_to_hyperbolic(2.0, -asymptote - 1.0e-6).is_err());
+        assert!(true_to_hyperbolic(2.0, PI).is_err());
+        assert!(true_to_parabolic(PI).is_err());
+        assert_eq!(true_to_hyperbolic(2.0, f64::NAN), Err(SolveError::NonFiniteInput));
+        assert_eq!(true_to_mean(2.0, 3.0), Err(SolveError::BeyondAsymptote { eccentricity: 2.0, true_anomaly: 3.0 }));
+    }
+
+    #[test]
+    fn test_anomaly_parabolic() {
+        for x in -1000..1000 {
+            let true_anomaly = x as f64 / 1000.0 * 3.1;
+            let parabolic_anomaly = true_to_parabolic(true_anomaly).unwrap();
+            assert!((parabolic_anomaly - (true_anomaly / 2.0).tan()).abs() < 1.0e-12 * parabolic_anomaly.abs().max(1.0));
+            assert!((parabolic_to_true(parabolic_anomaly) - true_anomaly).abs() < 1.0e-12);
+            assert!((true_to_parabolic(true_anomaly - 2.0 * PI).unwrap() - parabolic_anomaly).abs() < 1.0e-9 * parabolic_anomaly.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn test_anomaly_mean_to_true() {
+        for e in [0.0, 0.5, 0.99, 1.0, 1.01, 3.0] {
+            for x in -100..100 {
+                let mean_anomaly = x as f64 / 10.0;
+                let true_anomaly = mean_to_true(e, mean_anomaly).unwrap();
+                let round_trip = true_to_mean(e, true_anomaly).unwrap();
+                // Elliptic mean anomalies come back reduced into [0, 2pi)
+                let difference = round_trip - mean_anomaly;
+                let difference = if e < 1.0 { difference - 2.0 * PI * (difference / (2.0 * PI)).round() } else { difference };
+                assert!(difference.abs() < 1.0e-9, "{} {} {}", e, mean_anomaly, round_trip);
+            }
+        }
+        assert_eq!(Anomaly::from_true(1.0, 1.0), Ok(Anomaly::Parabolic(true_to_parabolic(1.0).unwrap())));
+        assert_eq!(Anomaly::from_true(-0.5, 1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: -0.5 }));
+        assert_eq!(mean_to_true(0.5, f64::NAN), Err(SolveError::NonFiniteInput));
+    }
+}
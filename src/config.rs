@@ -0,0 +1,89 @@
+//! How hard the solvers work. The elliptic and hyperbolic solvers iterate on different things, so
+//! each has its own presets, but they share the same knobs.
+
+use serde::{Deserialize, Serialize};
+
+use crate::float::Float;
+
+pub(crate) const MAX_ITERATIONS: usize = 50;
+/// Tolerance of the `Fast` presets, which is about as loose as can be before the starters
+/// themselves become the limiting factor
+const FAST_TOLERANCE: f64 = 1.0e-5;
+
+/// Presets trading speed for accuracy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precision {
+    /// Good to around 1e-5 radians, which is plenty for games and visualisation
+    Fast,
+    /// What `new` uses. Good to around 1e-10 radians for the elliptic solver, and 1e-11 relative
+    /// for the hyperbolic solver
+    Balanced,
+    /// Adds refinement steps to `Balanced` that evaluate Kepler's equation with its rounding errors
+    /// compensated, which takes the elliptic and hyperbolic solvers to within about an ulp of the
+    /// exact root for any eccentricity, near periapsis included (the worst cases measured are 1.2
+    /// and 1.9 ulp). The universal solver just takes one more step
+    Exact,
+}
+
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::{config::{Precision, SolverConfig}, ellipse::EllipseSolver};
+///
+/// fn example_config() {
+///     let game = EllipseSolver::with_config(0.5, SolverConfig::ellipse(Precision::Fast));
+///     let analysis = EllipseSolver::with_config(0.5, SolverConfig::ellipse(Precision::Exact));
+///     println!("{} {}", game.solve(1.2), analysis.solve(1.2));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SolverConfig<T = f64> {
+    /// The iteration has converged once its delta is smaller than `absolute_tolerance` plus
+    /// `relative_tolerance` times the current estimate. For the elliptic solver this applies to the
+    /// iteration of its `KeplerMethod` on Kepler's equation, unless the method has its own test as
+    /// `Enrke` does, for the hyperbolic solver to the Halley iteration on the Pade cubic that gives
+    /// the initial approximation, and for the universal solver to its Laguerre iteration
+    pub absolute_tolerance: T,
+    pub relative_tolerance: T,
+    /// The iteration gives up after this many steps; `try_solve` then returns `NotConverged`
+    pub max_iterations: usize,
+    /// Steps taken on Kepler's equation after the iteration has converged. For the elliptic and
+    /// hyperbolic solvers these are Halley steps with a compensated residual, from the converged
+    /// estimate and the initial approximation respectively, and for the universal solver they're
+    /// more Laguerre steps
+    pub refinement_steps: usize,
+}
+
+impl<T: Float> SolverConfig<T> {
+    pub fn ellipse(precision: Precision) -> Self {
+        let (absolute_tolerance, refinement_steps) = match precision {
+            Precision::Fast => (T::from_f64(FAST_TOLERANCE), 0),
+            Precision::Balanced => (T::DELTA_THRESHOLD, 0),
+            Precision::Exact => (T::DELTA_THRESHOLD, 2),
+        };
+        Self { absolute_tolerance, relative_tolerance: T::from_f64(0.0), max_iterations: MAX_ITERATIONS, refinement_steps }
+    }
+
+    pub fn hyperbola(precision: Precision) -> Self {
+        let (absolute_tolerance, refinement_steps) = match precision {
+            Precision::Fast => (T::from_f64(FAST_TOLERANCE), 0),
+            Precision::Balanced => (T::CUBIC_DELTA_THRESHOLD, 1),
+            Precision::Exact => (T::CUBIC_DELTA_THRESHOLD, 3),
+        };
+        Self { absolute_tolerance, relative_tolerance: T::from_f64(0.0), max_iterations: MAX_ITERATIONS, refinement_steps }
+    }
+
+    /// The universal anomaly grows without bound with the mean anomaly, so these presets have a
+    /// relative tolerance as well
+    pub fn universal(precision: Precision) -> Self {
+        let (tolerance, refinement_steps) = match precision {
+            Precision::Fast => (T::from_f64(FAST_TOLERANCE), 0),
+            Precision::Balanced => (T::DELTA_THRESHOLD, 0),
+            Precision::Exact => (T::DELTA_THRESHOLD, 1),
+        };
+        Self { absolute_tolerance: tolerance, relative_tolerance: tolerance, max_iterations: MAX_ITERATIONS, refinement_steps }
+    }
+
+    pub(crate) fn converged(&self, delta: T, estimate: T) -> bool {
+        delta.abs() < self.absolute_tolerance + self.relative_tolerance * estimate.abs()
+    }
+}
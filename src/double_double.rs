@@ -0,0 +1,219 @@
+//! Double-double arithmetic, which represents a number as the unevaluated sum of two f64s for
+//! about 106 bits of mantissa. Only what the reference solvers need is implemented. The algorithms
+//! are the usual error-free transformations from Dekker and from Hida, Li and Bailey's QD library.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::float::Float;
+
+/// 2pi, split so that `TAU_HI + TAU_LO` is correct to about 107 bits
+const TAU_HI: f64 = std::f64::consts::TAU;
+const TAU_LO: f64 = 2.449_293_598_294_706_4e-16;
+const LN_2_HI: f64 = std::f64::consts::LN_2;
+const LN_2_LO: f64 = 2.319_046_813_846_299_6e-17;
+/// Arguments are halved this many times before a Taylor series is summed, and the result is then
+/// doubled back up with the double angle formulas
+const HALVINGS: i32 = 3;
+/// Series are summed until the terms are this small relative to the sum
+const SERIES_THRESHOLD: f64 = 1.0e-34;
+
+/// Generic so that the solvers can use it to compensate their residuals in either precision
+pub(crate) fn two_sum<T: Float>(a: T, b: T) -> (T, T) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+/// Same as `two_sum`, but requires |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+pub(crate) fn two_product<T: Float>(a: T, b: T) -> (T, T) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(value: f64) -> Self {
+        Self { hi: value, lo: 0.0 }
+    }
+}
+
+impl DoubleDouble {
+    pub const ZERO: Self = Self { hi: 0.0, lo: 0.0 };
+    pub const ONE: Self = Self { hi: 1.0, lo: 0.0 };
+    pub const TAU: Self = Self { hi: TAU_HI, lo: TAU_LO };
+    pub const LN_2: Self = Self { hi: LN_2_HI, lo: LN_2_LO };
+
+    fn normalized(hi: f64, lo: f64) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        Self { hi, lo }
+    }
+
+    /// Rounds to the nearest f64
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    pub fn abs(self) -> Self {
+        if self.hi < 0.0 { -self } else { self }
+    }
+
+    /// Exact multiplication by a power of two
+    fn scale(self, power: i32) -> Self {
+        let factor = f64::powi(2.0, power);
+        Self { hi: self.hi * factor, lo: self.lo * factor }
+    }
+
+    /// Sums a Taylor series where the nth term is the previous term times `x` divided by
+    /// `divisor(n)`, stopping once the terms no longer affect the sum. The divisors are exact
+    /// integers so that no rounding creeps in through the coefficients
+    fn series(first: Self, x: Self, divisor: impl Fn(f64) -> f64) -> Self {
+        let mut term = first;
+        let mut sum = first;
+        let mut n = 1.0;
+        while term.hi.abs() > SERIES_THRESHOLD * sum.hi.abs() {
+            term = term * x / Self::from(divisor(n));
+            sum = sum + term;
+            n += 1.0;
+        }
+        sum
+    }
+
+    /// Returns the sine and cosine. The argument is reduced by 2pi in double-double, so accuracy
+    /// falls off slowly for arguments many revolutions out
+    pub fn sin_cos(self) -> (Self, Self) {
+        let revolutions = (self.hi / TAU_HI).round();
+        let x = (self - Self::TAU * Self::from(revolutions)).scale(-HALVINGS);
+        let x2 = x * x;
+        let mut sin = Self::series(x, x2, |n| -(2.0 * n) * (2.0 * n + 1.0));
+        let mut cos = Self::series(Self::ONE, x2, |n| -(2.0 * n - 1.0) * (2.0 * n));
+        for _ in 0..HALVINGS {
+            (sin, cos) = ((sin * cos).scale(1), (cos - sin) * (cos + sin));
+        }
+        (sin, cos)
+    }
+
+    pub fn exp(self) -> Self {
+        let powers = (self.hi / LN_2_HI).round();
+        let x = (self - Self::LN_2 * Self::from(powers)).scale(-HALVINGS);
+        // Sum exp(x) - 1 and square with (1 + u)^2 - 1 = u(2 + u), so the small part isn't lost
+        // against the one
+        let mut expm1 = Self::series(x, x, |n| n + 1.0);
+        for _ in 0..HALVINGS {
+            expm1 = expm1 * (expm1 + Self::from(2.0));
+        }
+        (expm1 + Self::ONE).scale(powers as i32)
+    }
+
+    /// Returns the hyperbolic sine and cosine. Small arguments use the Taylor series directly,
+    /// since sinh(x) = (exp(x) - exp(-x)) / 2 cancels badly
+    pub fn sinh_cosh(self) -> (Self, Self) {
+        if self.hi.abs() < 1.0 {
+            let x2 = self * self;
+            let sinh = Self::series(self, x2, |n| (2.0 * n) * (2.0 * n + 1.0));
+            let cosh = Self::series(Self::ONE, x2, |n| (2.0 * n - 1.0) * (2.0 * n));
+            return (sinh, cosh);
+        }
+        let exp = self.exp();
+        let exp_negative = Self::ONE / exp;
+        ((exp - exp_negative).scale(-1), (exp + exp_negative).scale(-1))
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        Self::normalized(s, e + f)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let (p, e) = two_product(self.hi, other.hi);
+        Self::normalized(p, e + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        // Long division, one f64 digit at a time
+        let q1 = self.hi / other.hi;
+        let r = self - other * Self::from(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * Self::from(q2);
+        let q3 = r.hi / other.hi;
+        Self::normalized(q1, q2) + Self::from(q3)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { hi: -self.hi, lo: -self.lo }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DoubleDouble;
+
+    fn assert_close(expected: DoubleDouble, actual: DoubleDouble, tolerance: f64) {
+        if (expected - actual).abs().hi > tolerance * expected.abs().hi.max(1.0) {
+            dbg!(expected, actual);
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_double_double() {
+        let third = DoubleDouble::ONE / DoubleDouble::from(3.0);
+        assert_close(DoubleDouble::ONE, third * DoubleDouble::from(3.0), 1.0e-32);
+        assert!(third.lo != 0.0);
+
+        for x in -200..200 {
+            let x = DoubleDouble::from(x as f64 / 10.0) + DoubleDouble::from(1.0e-20);
+            let (sin, cos) = x.sin_cos();
+            assert_close(DoubleDouble::ONE, sin * sin + cos * cos, 1.0e-30);
+            let (sin_double, cos_double) = (x * DoubleDouble::from(2.0)).sin_cos();
+            assert_close(sin_double, (sin * cos) * DoubleDouble::from(2.0), 1.0e-30);
+            assert_close(cos_double, cos * cos - sin * sin, 1.0e-30);
+            assert!((sin.to_f64() - x.to_f64().sin()).abs() < 1.0e-15);
+
+            let (sinh, cosh) = x.sinh_cosh();
+            if x.hi.abs() < 2.0 {
+                assert_close(DoubleDouble::ONE, cosh * cosh - sinh * sinh, 1.0e-30);
+            }
+            // sinh + cosh cancels for negative x, so only expect agreement relative to cosh
+            assert!((x.exp() - (sinh + cosh)).abs().hi < 1.0e-30 * cosh.hi);
+            assert_close(DoubleDouble::ONE, x.exp() * (-x).exp(), 1.0e-30);
+            assert!((sinh.to_f64() - x.to_f64().sinh()).abs() < 1.0e-15 * x.to_f64().cosh());
+        }
+        assert_close(DoubleDouble::from(2.0), DoubleDouble::LN_2.exp(), 1.0e-32);
+    }
+}
@@ -0,0 +1,284 @@
+//! Support for pushing automatic differentiation types through the solvers. The iteration only
+//! ever sees the real parts; the derivative parts are filled in afterwards by Newton steps on
+//! Kepler's equation taken in the dual arithmetic, starting from the converged root. Since the
+//! residual at the root is zero to working precision, each step leaves the real part alone and
+//! doubles the number of correct derivative orders, so one step is exact for first derivatives
+//! however loose the iteration threshold was.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::float::Float;
+
+/// A number with a real part of type `T` and some derivative parts, such as a dual or hyper-dual
+/// number. Plain floats implement it with no derivative parts.
+pub trait DualNumber<T: Float>:
+    Copy
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+{
+    /// The highest order of derivative carried, which decides how many correction steps are needed
+    const ORDER: u32;
+
+    /// A number with the given real part and all derivative parts zero
+    fn from_real(real: T) -> Self;
+    fn real(&self) -> T;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+}
+
+impl<T: Float> DualNumber<T> for T {
+    const ORDER: u32 = 0;
+
+    fn from_real(real: T) -> Self { real }
+    fn real(&self) -> T { *self }
+    fn sin(self) -> Self { Float::sin(self) }
+    fn cos(self) -> Self { Float::cos(self) }
+    fn sinh(self) -> Self { Float::sinh(self) }
+    fn cosh(self) -> Self { Float::cosh(self) }
+}
+
+/// Fills in the derivative parts of `root`, a root of the function `f` found on the real parts.
+/// `f` returns the function and its derivative
+pub(crate) fn implicit_correction<T: Float, D: DualNumber<T>>(root: T, f: impl Fn(D) -> (D, D)) -> D {
+    let mut x = D::from_real(root);
+    let mut order = 0;
+    while order < D::ORDER {
+        let (value, derivative) = f(x);
+        // Drop whatever real residual the iteration left, so the real part stays exactly `root`
+        let value = value - D::from_real(value.real());
+        x = x - value / derivative;
+        order = 2 * order + 1;
+    }
+    x
+}
+
+/// A first order dual number carrying derivatives with respect to `N` variables
+///
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::{dual::Dual, ellipse::EllipseSolver};
+///
+/// fn example_dual() {
+///     // Differentiate with respect to eccentricity (index 0) and mean anomaly (index 1)
+///     let eccentricity = Dual::<f64, 2>::variable(0.5, 0);
+///     let mean_anomaly = Dual::<f64, 2>::variable(1.2, 1);
+///     let eccentric_anomaly = EllipseSolver::new(0.5).solve_dual(eccentricity, mean_anomaly);
+///     println!("{} {:?}", eccentric_anomaly.real, eccentric_anomaly.dual);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual<T = f64, const N: usize = 1> {
+    pub real: T,
+    pub dual: [T; N],
+}
+
+impl<T: Float, const N: usize> Dual<T, N> {
+    pub fn new(real: T, dual: [T; N]) -> Self {
+        Self { real, dual }
+    }
+
+    pub fn constant(real: T) -> Self {
+        Self { real, dual: [T::from_f64(0.0); N] }
+    }
+
+    /// The variable with the given index, whose derivative with respect to itself is one
+    pub fn variable(real: T, index: usize) -> Self {
+        let mut dual = [T::from_f64(0.0); N];
+        dual[index] = T::from_f64(1.0);
+        Self { real, dual }
+    }
+
+    /// Applies a function with value `value` and derivative `derivative` at the real part
+    fn chain(self, value: T, derivative: T) -> Self {
+        Self { real: value, dual: self.dual.map(|d| d * derivative) }
+    }
+}
+
+impl<T: Float, const N: usize> Add for Dual<T, N> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self { real: self.real + other.real, dual: std::array::from_fn(|i| self.dual[i] + other.dual[i]) }
+    }
+}
+
+impl<T: Float, const N: usize> Sub for Dual<T, N> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self { real: self.real - other.real, dual: std::array::from_fn(|i| self.dual[i] - other.dual[i]) }
+    }
+}
+
+impl<T: Float, const N: usize> Mul for Dual<T, N> {
+    type Output = Self;
+
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, other: Self) -> Self {
+        Self {
+            real: self.real * other.real,
+            dual: std::array::from_fn(|i| self.dual[i] * other.real + self.real * other.dual[i]),
+        }
+    }
+}
+
+impl<T: Float, const N: usize> Div for Dual<T, N> {
+    type Output = Self;
+
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, other: Self) -> Self {
+        let real = self.real / other.real;
+        Self { real, dual: std::array::from_fn(|i| (self.dual[i] - real * other.dual[i]) / other.real) }
+    }
+}
+
+impl<T: Float, const N: usize> Neg for Dual<T, N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { real: -self.real, dual: self.dual.map(|d| -d) }
+    }
+}
+
+impl<T: Float, const N: usize> DualNumber<T> for Dual<T, N> {
+    const ORDER: u32 = 1;
+
+    fn from_real(real: T) -> Self { Self::constant(real) }
+    fn real(&self) -> T { self.real }
+    fn sin(self) -> Self { self.chain(self.real.sin(), self.real.cos()) }
+    fn cos(self) -> Self { self.chain(self.real.cos(), -self.real.sin()) }
+    fn sinh(self) -> Self { self.chain(self.real.sinh(), self.real.cosh()) }
+    fn cosh(self) -> Self { self.chain(self.real.cosh(), self.real.sinh()) }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::{Add, Div, Mul, Neg, Sub};
+
+    use crate::{config::{Precision, SolverConfig}, ellipse::EllipseSolver, hyperbola::HyperbolaSolver, method::Danby};
+
+    use super::{Dual, DualNumber};
+
+    /// A hyper-dual number x + a e1 + b e2 + c e1e2, where e1^2 = e2^2 = 0, to check second
+    /// derivatives come out exact with two correction steps
+    #[derive(Debug, Clone, Copy)]
+    struct HyperDual([f64; 4]);
+
+    impl HyperDual {
+        fn chain(self, f: f64, f_prime: f64, f_prime_prime: f64) -> Self {
+            let [_, a, b, c] = self.0;
+            Self([f, f_prime * a, f_prime * b, f_prime * c + f_prime_prime * a * b])
+        }
+    }
+
+    impl Add for HyperDual {
+        type Output = Self;
+        fn add(self, other: Self) -> Self { Self(std::array::from_fn(|i| self.0[i] + other.0[i])) }
+    }
+
+    impl Sub for HyperDual {
+        type Output = Self;
+        fn sub(self, other: Self) -> Self { self + -other }
+    }
+
+    impl Neg for HyperDual {
+        type Output = Self;
+        fn neg(self) -> Self { Self(self.0.map(|x| -x)) }
+    }
+
+    impl Mul for HyperDual {
+        type Output = Self;
+        fn mul(self, other: Self) -> Self {
+            let ([x, a, b, c], [y, d, e, f]) = (self.0, other.0);
+            Self([x * y, x * d + a * y, x * e + b * y, x * f + a * e + b * d + c * y])
+        }
+    }
+
+    impl Div for HyperDual {
+        type Output = Self;
+        fn div(self, other: Self) -> Self {
+            let y = other.0[0];
+            self * other.chain(1.0 / y, -1.0 / (y * y), 2.0 / (y * y * y))
+        }
+    }
+
+    impl DualNumber<f64> for HyperDual {
+        const ORDER: u32 = 2;
+        fn from_real(real: f64) -> Self { Self([real, 0.0, 0.0, 0.0]) }
+        fn real(&self) -> f64 { self.0[0] }
+        fn sin(self) -> Self { let x = self.0[0]; self.chain(x.sin(), x.cos(), -x.sin()) }
+        fn cos(self) -> Self { let x = self.0[0]; self.chain(x.cos(), -x.sin(), -x.cos()) }
+        fn sinh(self) -> Self { let x = self.0[0]; self.chain(x.sinh(), x.cosh(), x.sinh()) }
+        fn cosh(self) -> Self { let x = self.0[0]; self.chain(x.cosh(), x.sinh(), x.cosh()) }
+    }
+
+    fn assert_close(expected: f64, actual: f64) {
+        if (expected - actual).abs() > 1.0e-12 * expected.abs().max(1.0) {
+            dbg!(expected, actual);
+            panic!()
+        }
+    }
+
+    #[test]
+    fn test_dual_matches_partials() {
+        for e in [0.0, 0.3, 0.9] {
+            for x in -100..100 {
+                let m = x as f64 / 10.0;
+                let (expected, partials) = EllipseSolver::new(e).solve_with_partials(m);
+                let actual = EllipseSolver::new(e).solve_dual(Dual::<f64, 2>::variable(e, 0), Dual::variable(m, 1));
+                assert_eq!(actual.real, EllipseSolver::new(e).solve_unwrapped(m));
+                assert_close(expected, actual.real.rem_euclid(std::f64::consts::TAU));
+                assert_close(partials.d_eccentricity, actual.dual[0]);
+                assert_close(partials.d_mean_anomaly, actual.dual[1]);
+                assert_eq!(EllipseSolver::new(e).solve_dual(e, m), actual.real);
+                // The real part comes from the solver's own method and config
+                let solver = EllipseSolver::with_method(e, Danby, SolverConfig::ellipse(Precision::Fast));
+                assert_eq!(solver.solve_dual(Dual::<f64, 2>::variable(e, 0), Dual::variable(m, 1)).real, solver.solve_unwrapped(m));
+            }
+        }
+        for e in [1.1, 2.0, 10.0] {
+            for x in -100..100 {
+                let m = f64::powi(x as f64, 3) / 10000.0;
+                let (expected, partials) = HyperbolaSolver::new(e).solve_with_partials(m);
+                let actual = HyperbolaSolver::new(e).solve_dual(Dual::<f64, 2>::variable(e, 0), Dual::variable(m, 1));
+                assert_eq!(expected, actual.real);
+                let solver = HyperbolaSolver::with_config(e, SolverConfig::hyperbola(Precision::Exact));
+                assert_eq!(solver.solve_dual(Dual::<f64, 2>::variable(e, 0), Dual::variable(m, 1)).real, solver.solve(m));
+                assert_close(partials.d_eccentricity, actual.dual[0]);
+                assert_close(partials.d_mean_anomaly, actual.dual[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hyper_dual_second_derivatives() {
+        for e in [0.3, 0.9] {
+            for x in -100..100 {
+                let m = x as f64 / 10.0;
+                let (_, partials, second_partials) = EllipseSolver::new(e).solve_with_second_partials(m);
+                let actual = EllipseSolver::new(e).solve_dual(HyperDual([e, 1.0, 0.0, 0.0]), HyperDual([m, 0.0, 1.0, 0.0]));
+                assert_close(partials.d_eccentricity, actual.0[1]);
+                assert_close(partials.d_mean_anomaly, actual.0[2]);
+                assert_close(second_partials.d2_mean_anomaly_eccentricity, actual.0[3]);
+                let actual = EllipseSolver::new(e).solve_dual(HyperDual([e, 1.0, 1.0, 0.0]), HyperDual::from_real(m));
+                assert_close(second_partials.d2_eccentricity, actual.0[3]);
+            }
+        }
+        for e in [1.1, 2.0, 10.0] {
+            for x in -100..100 {
+                let m = f64::powi(x as f64, 3) / 10000.0;
+                let (_, _, second_partials) = HyperbolaSolver::new(e).solve_with_second_partials(m);
+                let actual = HyperbolaSolver::new(e).solve_dual(HyperDual::from_real(e), HyperDual([m, 1.0, 1.0, 0.0]));
+                assert_close(second_partials.d2_mean_anomaly, actual.0[3]);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_solve_dual_eccentricity_mismatch() {
+        EllipseSolver::new(0.5).solve_dual(Dual::<f64, 1>::variable(0.6, 0), Dual::from_real(1.0));
+    }
+}
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::SolveError, float::Float, kepler::Anomaly, vector::{cross, dot, norm, scale, sub}};
+
+/// Relative tolerance below which `OrbitalElements::from_state` treats an orbit as circular,
+/// equatorial or radial
+pub const SINGULARITY_TOLERANCE: f64 = 1.0e-11;
+
+/// The classical orbital elements. The size of the orbit is stored as the semi-latus rectum
+/// p = a(1 - e^2) rather than the semi-major axis, because a is infinite for a parabola while p is
+/// finite for every conic. Use `from_semi_major_axis` to build the elements from a, which should be
+/// negative for hyperbolas.
+///
+/// The mean anomaly is in the convention of the solver `KeplerSolver::new(eccentricity)` picks, so
+/// it's the parabolic mean anomaly sqrt(mu / (2 q^3)) * (t - T) for (near-)parabolic orbits. Angles
+/// are in radians, and the orientation angles follow the usual 3-1-3 convention.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OrbitalElements<T = f64> {
+    pub semi_latus_rectum: T,
+    pub eccentricity: T,
+    pub inclination: T,
+    pub longitude_of_ascending_node: T,
+    pub argument_of_periapsis: T,
+    pub mean_anomaly_at_epoch: T,
+    pub epoch: T,
+}
+
+impl<T: Float> OrbitalElements<T> {
+    /// `semi_major_axis` is negative for hyperbolas. Can't represent parabolas, since their
+    /// semi-major axis is infinite; set `semi_latus_rectum` directly for those
+    pub fn from_semi_major_axis(
+        semi_major_axis: T,
+        eccentricity: T,
+        inclination: T,
+        longitude_of_ascending_node: T,
+        argument_of_periapsis: T,
+        mean_anomaly_at_epoch: T,
+        epoch: T,
+    ) -> Self {
+        let one = T::from_f64(1.0);
+        let semi_latus_rectum = semi_major_axis * (one - eccentricity) * (one + eccentricity);
+        Self { semi_latus_rectum, eccentricity, inclination, longitude_of_ascending_node, argument_of_periapsis, mean_anomaly_at_epoch, epoch }
+    }
+
+    /// Negative for hyperbolas and infinite for parabolas
+    pub fn semi_major_axis(&self) -> T {
+        let one = T::from_f64(1.0);
+        self.semi_latus_rectum / ((one - self.eccentricity) * (one + self.eccentricity))
+    }
+
+    pub fn periapsis(&self) -> T {
+        self.semi_latus_rectum / (T::from_f64(1.0) + self.eccentricity)
+    }
+
+    /// Computes the elements of the orbit that passes through `position` with `velocity` at time
+    /// `epoch`. Angles are returned in [0, 2pi), apart from the mean anomaly, which comes from a
+    /// true anomaly in [-pi, pi). The angles that are undefined for some orbits follow these
+    /// conventions, using `SINGULARITY_TOLERANCE`:
+    /// - Equatorial orbits (inclination 0 or pi) have a longitude of ascending node of 0, so the
+    ///   argument of periapsis is measured from the x axis
+    /// - Circular orbits have an eccentricity and argument of periapsis of 0, so the mean anomaly is
+    ///   measured from the ascending node, and is the argument of latitude
+    /// - Circular equatorial orbits have both, so the mean anomaly is measured from the x axis, and
+    ///   is the true longitude
+    /// - Parabolic orbits, within `DEFAULT_PARABOLIC_TOLERANCE` of an eccentricity of 1, get the
+    ///   parabolic mean anomaly, like everywhere else in the crate
+    /// - Radial orbits have no angular momentum and so no orbital plane, and return an error
+    pub fn from_state(position: [T; 3], velocity: [T; 3], gravitational_parameter: T, epoch: T) -> Result<Self, SolveError> {
+        let c = T::from_f64;
+        if !position.iter().chain(&velocity).all(|x| x.is_finite()) || !gravitational_parameter.is_finite() || !epoch.is_finite() {
+            return Err(SolveError::NonFiniteInput);
+        }
+        let tolerance = c(SINGULARITY_TOLERANCE);
+        let radius = norm(position);
+        let speed = norm(velocity);
+        let angular_momentum = cross(position, velocity);
+        let angular_momentum_norm = norm(angular_momentum);
+        if angular_momentum_norm <= tolerance * radius * speed {
+            return Err(SolveError::RadialOrbit);
+        }
+
+        let semi_latus_rectum = angular_momentum_norm.powi(2) / gravitational_parameter;
+        let eccentricity_vector = scale(
+            sub(scale(position, speed.powi(2) - gravitational_parameter / radius), scale(velocity, dot(position, velocity))),
+            c(1.0) / gravitational_parameter);
+        let eccentricity = norm(eccentricity_vector);
+
+        // Points towards the ascending node, with the same length as the angular momentum's
+        // projection onto the equatorial plane
+        let node = [-angular_momentum[1], angular_momentum[0], c(0.0)];
+        let node_norm = norm(node);
+        let inclination = T::atan2(node_norm, angular_momentum[2]);
+        let equatorial = node_norm <= tolerance * angular_momentum_norm;
+        let (longitude_of_ascending_node, node_direction) = if equatorial {
+            (c(0.0), [c(1.0), c(0.0), c(0.0)])
+        } else {
+            (wrap_angle(T::atan2(node[1], node[0])), scale(node, c(1.0) / node_norm))
+        };
+
+        // Angles in the orbital plane are measured from the node, in the direction of motion
+        let ahead_of_node_direction = cross(scale(angular_momentum, c(1.0) / angular_momentum_norm), node_direction);
+        let angle_from_node = |v: [T; 3]| T::atan2(dot(v, ahead_of_node_direction), dot(v, node_direction));
+        let circular = eccentricity <= tolerance;
+        let (eccentricity, argument_of_periapsis) = if circular {
+            (c(0.0), c(0.0))
+        } else {
+            (eccentricity, wrap_angle(angle_from_node(eccentricity_vector)))
+        };
+        let true_anomaly = wrap_angle(angle_from_node(position) - argument_of_periapsis + T::PI) - T::PI;
+        let mean_anomaly_at_epoch = Anomaly::from_true(eccentricity, true_anomaly)?.to_mean(eccentricity);
+
+        Ok(Self { semi_latus_rectum, eccentricity, inclination, longitude_of_ascending_node, argument_of_periapsis, mean_anomaly_at_epoch, epoch })
+    }
+}
+
+/// Maps any angle to [0, 2pi)
+fn wrap_angle<T: Float>(angle: T) -> T {
+    let wrapped = angle - T::TAU * (angle / T::TAU).floor();
+    if wrapped < T::TAU { wrapped } else { T::from_f64(0.0) }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use crate::{error::SolveError, orbit::Orbit};
+
+    use super::OrbitalElements;
+
+    const MU: f64 = 3.986_004_418e14;
+
+    fn assert_states_match(expected: ([f64; 3], [f64; 3]), actual: ([f64; 3], [f64; 3]), tolerance: f64) {
+        let scale = |v: [f64; 3]| v.iter().map(|x| x.abs()).fold(0.0, f64::max);
+        for i in 0..3 {
+            if (expected.0[i] - actual.0[i]).abs() > tolerance * scale(expected.0) || (expected.1[i] - actual.1[i]).abs() > tolerance * scale(expected.1) {
+                dbg!(expected, actual);
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_elements_semi_major_axis() {
+        for (a, e) in [(1.0e7_f64, 0.0), (1.0e7, 0.5), (-1.0e7, 1.5), (-3.0, 100.0)] {
+            let elements = OrbitalElements::from_semi_major_axis(a, e, 0.1, 0.2, 0.3, 0.4, 0.5);
+            assert!((elements.semi_major_axis() - a).abs() < 1.0e-12 * a.abs());
+            assert!((elements.periapsis() - a * (1.0 - e)).abs() < 1.0e-12 * a.abs() * (1.0 - e).abs());
+        }
+        let parabola = OrbitalElements { semi_latus_rectum: 2.0, eccentricity: 1.0, inclination: 0.0, longitude_of_ascending_node: 0.0, argument_of_periapsis: 0.0, mean_anomaly_at_epoch: 0.0, epoch: 0.0 };
+        assert_eq!(parabola.semi_major_axis(), f64::INFINITY);
+        assert_eq!(parabola.periapsis(), 1.0);
+    }
+
+    #[test]
+    fn test_elements_from_state_round_trip() {
+        let eccentricities = [0.0, 1.0e-13, 1.0e-6, 0.3, 0.9, 0.999_999_5, 1.0, 1.000_000_5, 1.5, 10.0];
+        let inclinations = [0.0, 1.0e-14, 0.5, PI / 2.0, 3.0, PI];
+        for e in eccentricities {
+            for i in inclinations {
+                for (node, periapsis) in [(0.0, 0.0), (1.0, 2.0), (5.0, 6.0)] {
+                    let elements = OrbitalElements { semi_latus_rectum: 1.0e7, eccentricity: e, inclination: i, longitude_of_ascending_node: node, argument_of_periapsis: periapsis, mean_anomaly_at_epoch: 0.3, epoch: 0.0 };
+                    let orbit = Orbit::new(elements, MU);
+                    for t in [-3000.0, -100.0, 0.0, 700.0, 5000.0] {
+                        let state = orbit.state_at(t);
+                        let round_trip = OrbitalElements::from_state(state.0, state.1, MU, t).unwrap();
+                        assert_states_match(state, Orbit::new(round_trip, MU).state_at(t), 1.0e-10);
+                        // And the orbit should be the same at other times too
+                        assert_states_match(orbit.state_at(t + 1000.0), Orbit::new(round_trip, MU).state_at(t + 1000.0), 1.0e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_elements_from_state() {
+        let elements = OrbitalElements::from_semi_major_axis(7.0e6, 0.1, 0.5, 1.0, 2.0, 0.4, 10.0);
+        let (position, velocity) = Orbit::new(elements, MU).state_at(10.0);
+        let actual = OrbitalElements::from_state(position, velocity, MU, 10.0).unwrap();
+        let expected = [elements.semi_latus_rectum / 1.0e6, elements.eccentricity, elements.inclination, elements.longitude_of_ascending_node, elements.argument_of_periapsis, elements.mean_anomaly_at_epoch, elements.epoch];
+        let actual = [actual.semi_latus_rectum / 1.0e6, actual.eccentricity, actual.inclination, actual.longitude_of_ascending_node, actual.argument_of_periapsis, actual.mean_anomaly_at_epoch, actual.epoch];
+        for (expected, actual) in expected.iter().zip(&actual) {
+            assert!((expected - actual).abs() < 1.0e-12, "{:?} {:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_elements_from_state_singular() {
+        // Circular equatorial, so the mean anomaly is the true longitude
+        let speed = (MU / 7.0e6_f64).sqrt();
+        let elements = OrbitalElements::from_state([0.0, 7.0e6, 0.0], [-speed, 0.0, 0.0], MU, 0.0).unwrap();
+        assert_eq!((elements.eccentricity, elements.inclination, elements.longitude_of_ascending_node, elements.argument_of_periapsis), (0.0, 0.0, 0.0, 0.0));
+        assert!((elements.mean_anomaly_at_epoch - PI / 2.0).abs() < 1.0e-12);
+
+        // Retrograde circular equatorial, so the true longitude is measured clockwise
+        let elements = OrbitalElements::from_state([0.0, 7.0e6, 0.0], [speed, 0.0, 0.0], MU, 0.0).unwrap();
+        assert_eq!((elements.inclination, elements.longitude_of_ascending_node), (PI, 0.0));
+        assert!((elements.mean_anomaly_at_epoch + PI / 2.0).abs() < 1.0e-12);
+
+        // Circular inclined, so the mean anomaly is the argument of latitude
+        let elements = OrbitalElements::from_state([0.0, 0.0, 7.0e6], [0.0, speed, 0.0], MU, 0.0).unwrap();
+        assert_eq!((elements.eccentricity, elements.argument_of_periapsis), (0.0, 0.0));
+        assert!((elements.inclination - PI / 2.0).abs() < 1.0e-12 && (elements.longitude_of_ascending_node - 1.5 * PI).abs() < 1.0e-12);
+        assert!((elements.mean_anomaly_at_epoch - PI / 2.0).abs() < 1.0e-12);
+
+        // Parabolic, so the mean anomaly is the parabolic one
+        let escape_speed = (2.0 * MU / 7.0e6_f64).sqrt();
+        let elements = OrbitalElements::from_state([7.0e6, 0.0, 0.0], [0.0, escape_speed, 0.0], MU, 0.0).unwrap();
+        assert!((elements.eccentricity - 1.0).abs() < 1.0e-12 && elements.mean_anomaly_at_epoch.abs() < 1.0e-12);
+        assert!((elements.periapsis() - 7.0e6).abs() < 1.0e-6);
+
+        assert_eq!(OrbitalElements::from_state([7.0e6, 0.0, 0.0], [1000.0, 0.0, 0.0], MU, 0.0), Err(SolveError::RadialOrbit));
+        assert_eq!(OrbitalElements::from_state([0.0, 0.0, 0.0], [1000.0, 0.0, 0.0], MU, 0.0), Err(SolveError::RadialOrbit));
+        assert_eq!(OrbitalElements::from_state([f64::NAN, 0.0, 0.0], [1000.0, 0.0, 0.0], MU, 0.0), Err(SolveError::NonFiniteInput));
+    }
+}
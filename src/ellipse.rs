@@ -1,17 +1,31 @@
-use std::f64::consts::PI;
-
 use serde::{Deserialize, Serialize};
 
-const DELTA_THRESHOLD: f64 = 1.0e-10;
+use crate::{config::{Precision, SolverConfig}, double_double::{two_product, two_sum}, dual::{implicit_correction, DualNumber}, error::SolveError, float::Float, interval::{certify, Interval}, method::{Contour, KeplerMethod, Laguerre, Piecewise}, partials::{Partials, SecondPartials}, report::SolveReport};
+
+const BATCH_CHUNK: usize = 64;
 
-fn laguerre_delta(f: f64, f_prime: f64, f_prime_prime: f64) -> f64 {
-    let n: f64 = 2.0; // N=2, modified Newton-Raphson
-    let a = (n-1.0).powi(2) * f_prime.powi(2) - n*(n-1.0)*f*f_prime_prime;
-    let mut b = f64::sqrt(a.abs());
+pub(crate) fn laguerre_delta<T: Float>(f: T, f_prime: T, f_prime_prime: T) -> T {
+    let n = T::from_f64(2.0); // N=2, modified Newton-Raphson
+    let one = T::from_f64(1.0);
+    let a = (n-one).powi(2) * f_prime.powi(2) - n*(n-one)*f*f_prime_prime;
+    let mut b = T::sqrt(a.abs());
     b = b.abs() * f_prime.signum(); // prevent catastrophic cancellation
     - (n*f) / (f_prime + b)
 }
 
+/// Returns the mean anomaly reduced into [-pi, pi] and the number of revolutions that were removed
+pub(crate) fn reduce_mean_anomaly<T: Float>(mean_anomaly: T) -> (T, T) {
+    let revolutions = (mean_anomaly / T::TAU).round();
+    if revolutions == T::from_f64(0.0) {
+        return (mean_anomaly, revolutions);
+    }
+    // Cody-Waite reduction with 2pi split into TAU and the remainder, so that reducing a mean
+    // anomaly thousands of revolutions out doesn't multiply the rounding error in TAU by the number
+    // of revolutions; the fused multiply-adds mean each step is rounded only once
+    let reduced = (-revolutions).mul_add(T::TAU, mean_anomaly);
+    ((-revolutions).mul_add(T::TAU_LO, reduced), revolutions)
+}
+
 /// ## Example
 /// ```rs
 /// use std::f64::consts::PI;
@@ -25,47 +39,407 @@ fn laguerre_delta(f: f64, f_prime: f64, f_prime_prime: f64) -> f64 {
 /// }
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EllipseSolver {
-    eccentricity: f64
+pub struct EllipseSolver<T = f64, M = Laguerre> {
+    eccentricity: T,
+    config: SolverConfig<T>,
+    method: M,
+}
+
+impl<T: Float> EllipseSolver<T> {
+    pub fn new(eccentricity: T) -> Self {
+        Self::with_config(eccentricity, SolverConfig::ellipse(Precision::Balanced))
+    }
+
+    pub fn with_config(eccentricity: T, config: SolverConfig<T>) -> Self {
+        Self::with_method(eccentricity, Laguerre, config)
+    }
+}
+
+impl<T: Float> EllipseSolver<T, Contour<T>> {
+    /// Uses `Contour`, with the contours laid out for `eccentricity`
+    pub fn contour(eccentricity: T, config: SolverConfig<T>) -> Self {
+        Self::with_method(eccentricity, Contour::new(eccentricity), config)
+    }
+}
+
+impl<T: Float> EllipseSolver<T, Piecewise<T>> {
+    /// Uses `Piecewise`, with the table fitted for `eccentricity`
+    pub fn piecewise(eccentricity: T, config: SolverConfig<T>) -> Self {
+        Self::with_method(eccentricity, Piecewise::new(eccentricity), config)
+    }
 }
 
-impl EllipseSolver {
-    pub fn new(eccentricity: f64) -> Self {
-        Self { eccentricity }
+impl<T: Float, M: KeplerMethod<T>> EllipseSolver<T, M> {
+    /// Uses `method` in place of the default Tommasini seed and Laguerre iteration. If the method
+    /// has its own convergence test, as `Enrke` does, the tolerances in `config` are unused
+    pub fn with_method(eccentricity: T, method: M, config: SolverConfig<T>) -> Self {
+        Self { eccentricity, config, method }
+    }
+
+    /// Works with all values of mean anomaly, returns the eccentric anomaly in [0, 2pi). Always terminates
+    pub fn solve(&self, mean_anomaly: T) -> T {
+        let ((eccentric_anomaly, _, _), _, _) = self.iterate(mean_anomaly);
+        wrap(eccentric_anomaly)
+    }
+
+    /// Same as `solve`, but also returns the sine and cosine of the eccentric anomaly, which are
+    /// left over from the last iteration so cost nothing extra. These are also the sine and cosine
+    /// of the anomaly returned by `solve_unwrapped`
+    pub fn solve_with_trig(&self, mean_anomaly: T) -> (T, T, T) {
+        let ((eccentric_anomaly, sin, cos), _, _) = self.iterate(mean_anomaly);
+        (wrap(eccentric_anomaly), sin, cos)
+    }
+
+    /// Same as `solve`, but also returns the derivatives of the eccentric anomaly with respect to
+    /// the mean anomaly and eccentricity
+    pub fn solve_with_partials(&self, mean_anomaly: T) -> (T, Partials<T>) {
+        let (eccentric_anomaly, sin, cos) = self.solve_with_trig(mean_anomaly);
+        // Differentiating E - e sin(E) = M gives (1 - e cos(E)) dE = dM + sin(E) de
+        let derivative = T::from_f64(1.0) - self.eccentricity * cos;
+        (eccentric_anomaly, Partials { d_mean_anomaly: T::from_f64(1.0) / derivative, d_eccentricity: sin / derivative })
     }
 
-    /// Works for 0 < `mean_anomaly` < 2pi
-    pub fn solve(&self, mean_anomaly: f64) -> f64 {
-        // Choosing an initial seed: https://www.aanda.org/articles/aa/full_html/2022/02/aa41423-21/aa41423-21.html#S5
-        // Yes, they're actually serious about that 0.999999 thing (lmao)
-        let mut eccentric_anomaly = mean_anomaly
-            + (0.999_999 * 4.0 * self.eccentricity * mean_anomaly * (PI - mean_anomaly))
-            / (8.0 * self.eccentricity * mean_anomaly + 4.0 * self.eccentricity * (self.eccentricity - PI) + PI.powi(2));
+    /// Same as `solve_with_partials`, but also returns the second derivatives
+    pub fn solve_with_second_partials(&self, mean_anomaly: T) -> (T, Partials<T>, SecondPartials<T>) {
+        let (eccentric_anomaly, sin, cos) = self.solve_with_trig(mean_anomaly);
+        let ec = self.eccentricity;
+        let derivative = T::from_f64(1.0) - ec * cos;
+        let derivative3 = derivative.powi(3);
+        let partials = Partials { d_mean_anomaly: T::from_f64(1.0) / derivative, d_eccentricity: sin / derivative };
+        let second_partials = SecondPartials {
+            d2_mean_anomaly: -ec * sin / derivative3,
+            d2_mean_anomaly_eccentricity: (cos - ec) / derivative3,
+            d2_eccentricity: (T::from_f64(2.0) * sin * cos * derivative - ec * sin.powi(3)) / derivative3,
+        };
+        (eccentric_anomaly, partials, second_partials)
+    }
+
+    /// Works with all values of mean anomaly, returns the eccentric anomaly plus 2pi for every
+    /// revolution, so the output is continuous in the mean anomaly. Always terminates
+    pub fn solve_unwrapped(&self, mean_anomaly: T) -> T {
+        let ((eccentric_anomaly, _, _), revolutions, _) = self.iterate(mean_anomaly);
+        unwrap(eccentric_anomaly, revolutions)
+    }
+
+    /// Solves for the eccentric anomaly with a dual number eccentricity and mean anomaly, returning
+    /// the same continuous value as `solve_unwrapped` with its derivative parts filled in by
+    /// implicitly differentiating Kepler's equation. The real part of `eccentricity` has to be the
+    /// solver's eccentricity
+    pub fn solve_dual<D: DualNumber<T>>(&self, eccentricity: D, mean_anomaly: D) -> D {
+        debug_assert!(eccentricity.real() == self.eccentricity, "the real part of eccentricity must be the solver's eccentricity");
+        let root = self.solve_unwrapped(mean_anomaly.real());
+        implicit_correction(root, |eccentric_anomaly: D| {
+            let f = eccentric_anomaly - eccentricity * eccentric_anomaly.sin() - mean_anomaly;
+            let f_prime = D::from_real(T::from_f64(1.0)) - eccentricity * eccentric_anomaly.cos();
+            (f, f_prime)
+        })
+    }
+
+    /// Same as `solve`, but also reports the residual and the number of iterations, for diagnosing
+    /// bad results
+    pub fn solve_report(&self, mean_anomaly: T) -> SolveReport<T> {
+        let (reduced_mean_anomaly, _) = reduce_mean_anomaly(mean_anomaly);
+        let ((eccentric_anomaly, sin, _), iterations, converged) = self.iterate_reduced(reduced_mean_anomaly.abs());
+        let sign = reduced_mean_anomaly.signum();
+        let residual = (eccentric_anomaly - self.eccentricity * sin - reduced_mean_anomaly.abs()) * sign;
+        SolveReport { anomaly: wrap(eccentric_anomaly * sign), residual, iterations, converged, branch: None }
+    }
+
+    /// Works with all values of mean anomaly, returns an error rather than a garbage value if the
+    /// input is invalid or the iteration fails to converge
+    pub fn try_solve(&self, mean_anomaly: T) -> Result<T, SolveError> {
+        self.try_iterate(mean_anomaly).map(|(eccentric_anomaly, _)| wrap(eccentric_anomaly))
+    }
+
+    /// Unwrapped version of `try_solve`
+    pub fn try_solve_unwrapped(&self, mean_anomaly: T) -> Result<T, SolveError> {
+        self.try_iterate(mean_anomaly).map(|(eccentric_anomaly, revolutions)| unwrap(eccentric_anomaly, revolutions))
+    }
+
+    fn try_iterate(&self, mean_anomaly: T) -> Result<(T, T), SolveError> {
+        if !mean_anomaly.is_finite() || !self.eccentricity.is_finite() {
+            return Err(SolveError::NonFiniteInput);
+        }
+        if self.eccentricity < T::from_f64(0.0) || self.eccentricity >= T::from_f64(1.0) {
+            return Err(SolveError::EccentricityOutOfRange { eccentricity: self.eccentricity.to_f64() });
+        }
+        match self.iterate(mean_anomaly) {
+            ((eccentric_anomaly, _, _), revolutions, true) => Ok((eccentric_anomaly, revolutions)),
+            (_, _, false) => Err(SolveError::NotConverged { iterations: self.config.max_iterations }),
+        }
+    }
+
+    /// Returns the eccentric anomaly in [-pi, pi] with its sine and cosine, the number of
+    /// revolutions removed from the mean anomaly to get there, and whether the iteration converged
+    fn iterate(&self, mean_anomaly: T) -> ((T, T, T), T, bool) {
+        // Kepler's equation is odd, so we only need to solve for 0 <= M <= pi and flip the sign
+        let (reduced_mean_anomaly, revolutions) = reduce_mean_anomaly(mean_anomaly);
+        let ((eccentric_anomaly, sin, cos), _, converged) = self.iterate_reduced(reduced_mean_anomaly.abs());
+        let sign = reduced_mean_anomaly.signum();
+        ((eccentric_anomaly * sign, sin * sign, cos), revolutions, converged)
+    }
+
+    /// Works for 0 <= `mean_anomaly` <= pi. Also returns the number of steps evaluated
+    fn iterate_reduced(&self, mean_anomaly: T) -> ((T, T, T), usize, bool) {
+        if M::NON_ITERATIVE {
+            let seed = self.method.seed_with_trig(self.eccentricity, mean_anomaly);
+            // The seed is the one step a non-iterative method takes
+            return (self.refine(mean_anomaly, seed), 1 + self.config.refinement_steps, true);
+        }
+        let mut eccentric_anomaly = self.method.seed(self.eccentricity, mean_anomaly);
 
-        // Iteration using laguerre method
         // According to this 1985 paper laguerre should practially always converge (they tested it 500,000 times on different values)
         // https://link.springer.com/content/pdf/10.1007/bf01230852.pdf
-        loop {
-            let sin_eccentric_anomaly = eccentric_anomaly.sin();
-            let cos_eccentric_anomaly = eccentric_anomaly.cos();
-            let f = mean_anomaly - eccentric_anomaly + self.eccentricity*sin_eccentric_anomaly;
-            let f_prime = -1.0 + self.eccentricity*cos_eccentric_anomaly;
-            let f_prime_prime = -self.eccentricity*sin_eccentric_anomaly;
-            let delta = laguerre_delta(f, f_prime, f_prime_prime);
-            if delta.abs() < DELTA_THRESHOLD {
-                break;
+        // The iteration count is still capped so that NaNs, bad eccentricities or less reliable
+        // methods can't hang the caller
+        for iteration in 1..=self.config.max_iterations {
+            let (sin, cos) = (eccentric_anomaly.sin(), eccentric_anomaly.cos());
+            let delta = self.method.delta(self.eccentricity, mean_anomaly, eccentric_anomaly, sin, cos);
+            if self.method.converged(&self.config, delta, eccentric_anomaly, self.eccentricity, cos) {
+                // Usually the delta isn't applied on the final iteration, so sin and cos are still current
+                let converged = if M::APPLIES_FINAL_DELTA {
+                    let (sin, cos) = rotate(sin, cos, delta);
+                    (eccentric_anomaly + delta, sin, cos)
+                } else {
+                    (eccentric_anomaly, sin, cos)
+                };
+                let refined = self.refine(mean_anomaly, converged);
+                return (refined, iteration + self.config.refinement_steps, true);
             }
             eccentric_anomaly += delta;
         }
+        ((eccentric_anomaly, eccentric_anomaly.sin(), eccentric_anomaly.cos()), self.config.max_iterations, false)
+    }
+
+    /// Takes the configured number of refinement steps from a converged estimate and its sine and
+    /// cosine. These are Halley steps with the residual from `kepler_residual`, so that they get
+    /// within about an ulp of the root even where E - e sin(E) - M cancels. The sine and cosine of
+    /// the estimate may come from the addition formulas, so the steps evaluate their own to give
+    /// exactly the same results as `solve_into`
+    fn refine(&self, mean_anomaly: T, converged: (T, T, T)) -> (T, T, T) {
+        if self.config.refinement_steps == 0 {
+            return converged;
+        }
+        let mut eccentric_anomaly = converged.0;
+        for _ in 0..self.config.refinement_steps {
+            let (sin, cos) = (eccentric_anomaly.sin(), eccentric_anomaly.cos());
+            eccentric_anomaly += refinement_delta(self.eccentricity, mean_anomaly, eccentric_anomaly, sin, cos);
+        }
+        (eccentric_anomaly, eccentric_anomaly.sin(), eccentric_anomaly.cos())
+    }
+
+    /// Solves every element of `mean_anomalies` into the same index of `out`. Gives exactly the
+    /// same results as calling `solve` on each element, but is laid out so that the compiler can
+    /// vectorize it
+    /// # Panics
+    /// If the slices have different lengths
+    pub fn solve_into(&self, mean_anomalies: &[T], out: &mut [T]) {
+        assert_eq!(mean_anomalies.len(), out.len(), "mean_anomalies and out must be the same length");
+        let eccentricities = [self.eccentricity; BATCH_CHUNK];
+        for (mean_anomalies, out) in mean_anomalies.chunks(BATCH_CHUNK).zip(out.chunks_mut(BATCH_CHUNK)) {
+            solve_chunk(&eccentricities[..mean_anomalies.len()], mean_anomalies, out, &self.config, &self.method);
+        }
+    }
+}
+
+impl<M: KeplerMethod<f64>> EllipseSolver<f64, M> {
+    /// Returns an interval guaranteed to contain the exact solution of Kepler's equation for the
+    /// given eccentricity and mean anomaly, accounting for rounding, or `NotCertified` if the
+    /// interval Newton method couldn't prove one. The interval is around the continuous anomaly
+    /// returned by `solve_unwrapped`, and is usually a few ulps wide
+    pub fn solve_certified(&self, mean_anomaly: f64) -> Result<Interval, SolveError> {
+        let estimate = self.try_solve_unwrapped(mean_anomaly)?;
+        let ec = Interval::point(self.eccentricity);
+        let mean_anomaly = Interval::point(mean_anomaly);
+        certify(
+            estimate,
+            |eccentric_anomaly| eccentric_anomaly - ec * eccentric_anomaly.sin_cos().0 - mean_anomaly,
+            |eccentric_anomaly| Interval::point(1.0) - ec * eccentric_anomaly.sin_cos().1,
+        )
+    }
+}
+
+impl EllipseSolver<f64> {
+    /// Solves 4 mean anomalies at once. With the `simd` feature on x86_64 this runs the Laguerre
+    /// iteration on SSE2 or AVX vectors, with polynomial sine and cosine, and agrees with `solve`
+    /// to within the convergence threshold for any mean anomaly. Otherwise it calls `solve` on each
+    /// lane. Only the default Laguerre method has a kernel
+    pub fn solve_x4(&self, mean_anomalies: [f64; 4]) -> [f64; 4] {
+        self.solve_lanes(mean_anomalies)
+    }
+
+    /// Same as `solve_x4`, but with 8 lanes
+    pub fn solve_x8(&self, mean_anomalies: [f64; 8]) -> [f64; 8] {
+        self.solve_lanes(mean_anomalies)
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fn solve_lanes<const N: usize>(&self, mean_anomalies: [f64; N]) -> [f64; N] {
+        crate::simd::solve_ellipse(self.eccentricity, mean_anomalies, &self.config)
+    }
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    fn solve_lanes<const N: usize>(&self, mean_anomalies: [f64; N]) -> [f64; N] {
+        mean_anomalies.map(|mean_anomaly| self.solve(mean_anomaly))
+    }
+}
+
+/// Solves each pair of `eccentricities` and `mean_anomalies` into the same index of `out`. Gives
+/// exactly the same results as `EllipseSolver::new(eccentricity).solve(mean_anomaly)` on each pair,
+/// but is laid out so that the compiler can vectorize it
+/// # Panics
+/// If the slices have different lengths
+pub fn solve_batch<T: Float>(eccentricities: &[T], mean_anomalies: &[T], out: &mut [T]) {
+    assert_eq!(eccentricities.len(), mean_anomalies.len(), "eccentricities and mean_anomalies must be the same length");
+    assert_eq!(mean_anomalies.len(), out.len(), "mean_anomalies and out must be the same length");
+    let chunks = eccentricities.chunks(BATCH_CHUNK)
+        .zip(mean_anomalies.chunks(BATCH_CHUNK))
+        .zip(out.chunks_mut(BATCH_CHUNK));
+    let config = SolverConfig::ellipse(Precision::Balanced);
+    for ((eccentricities, mean_anomalies), out) in chunks {
+        solve_chunk(eccentricities, mean_anomalies, out, &config, &Laguerre);
+    }
+}
+
+/// Runs each stage of the solver as a separate pass over the whole chunk, rather than running each
+/// element through the data-dependent loop one at a time. Elements that have converged keep being
+/// computed but stop being updated, so the results match the scalar path exactly
+fn solve_chunk<T: Float, M: KeplerMethod<T>>(eccentricities: &[T], mean_anomalies: &[T], out: &mut [T], config: &SolverConfig<T>, method: &M) {
+    let n = mean_anomalies.len();
+    let mut reduced_mean_anomalies = [T::from_f64(0.0); BATCH_CHUNK];
+    let mut signs = [T::from_f64(0.0); BATCH_CHUNK];
+    let mut done = [M::NON_ITERATIVE; BATCH_CHUNK];
+
+    for i in 0..n {
+        let (reduced_mean_anomaly, _) = reduce_mean_anomaly(mean_anomalies[i]);
+        reduced_mean_anomalies[i] = reduced_mean_anomaly.abs();
+        signs[i] = reduced_mean_anomaly.signum();
+        out[i] = method.seed(eccentricities[i], reduced_mean_anomalies[i]);
+    }
+
+    // Non-iterative methods start out done, and go straight to refinement
+    let max_iterations = if M::NON_ITERATIVE { 0 } else { config.max_iterations };
+    for _ in 0..max_iterations {
+        for i in 0..n {
+            let (sin, cos) = (out[i].sin(), out[i].cos());
+            let delta = method.delta(eccentricities[i], reduced_mean_anomalies[i], out[i], sin, cos);
+            let converged = method.converged(config, delta, out[i], eccentricities[i], cos);
+            // The final delta is only applied on the iteration that converges
+            out[i] += if done[i] || (converged && !M::APPLIES_FINAL_DELTA) { T::from_f64(0.0) } else { delta };
+            done[i] = done[i] || converged;
+        }
+        if done[..n].iter().all(|done| *done) {
+            break;
+        }
+    }
+
+    // Unconverged elements aren't refined, as in the scalar path
+    for _ in 0..config.refinement_steps {
+        for i in 0..n {
+            let delta = refinement_delta(eccentricities[i], reduced_mean_anomalies[i], out[i], out[i].sin(), out[i].cos());
+            out[i] += if done[i] { delta } else { T::from_f64(0.0) };
+        }
+    }
+
+    for i in 0..n {
+        out[i] = wrap(out[i] * signs[i]);
+    }
+}
+
+/// Works for 0 <= `mean_anomaly` <= pi
+pub(crate) fn seed<T: Float>(ec: T, mean_anomaly: T) -> T {
+    // Choosing an initial seed: https://www.aanda.org/articles/aa/full_html/2022/02/aa41423-21/aa41423-21.html#S5
+    // Yes, they're actually serious about that 0.999999 thing (lmao)
+    let pi = T::PI;
+    mean_anomaly
+        + (T::from_f64(0.999_999 * 4.0) * ec * mean_anomaly * (pi - mean_anomaly))
+        / (T::from_f64(8.0) * ec * mean_anomaly + T::from_f64(4.0) * ec * (ec - pi) + pi.powi(2))
+}
+
+/// `sin_eccentric_anomaly` and `cos_eccentric_anomaly` are passed in so the caller can keep them
+pub(crate) fn laguerre_step<T: Float>(ec: T, mean_anomaly: T, eccentric_anomaly: T, sin_eccentric_anomaly: T, cos_eccentric_anomaly: T) -> T {
+    let f = mean_anomaly - eccentric_anomaly + ec*sin_eccentric_anomaly;
+    let f_prime = -T::from_f64(1.0) + ec*cos_eccentric_anomaly;
+    let f_prime_prime = -ec*sin_eccentric_anomaly;
+    laguerre_delta(f, f_prime, f_prime_prime)
+}
+
+/// E - e sin(E) - M, with the rounding errors of the products and sums compensated. For |E| < 1
+/// the equation is rearranged to (1 - e) E + e (E - sin(E)) - M, with E - sin(E) summed as a series,
+/// since near periapsis at high eccentricity E and e sin(E) cancel and the rounding error in sin(E)
+/// alone would be many ulps of M. Elsewhere 1 - e cos(E) is at least 1 - cos(1), so the rounding
+/// error in `sin_eccentric_anomaly` only moves the root by a fraction of an ulp
+pub(crate) fn kepler_residual<T: Float>(ec: T, mean_anomaly: T, eccentric_anomaly: T, sin_eccentric_anomaly: T) -> T {
+    let c = T::from_f64;
+    if eccentric_anomaly.abs() < c(1.0) {
+        // E - sin(E) = E^3 / 3! - E^5 / 5! + ..., to the E^19 term
+        let x2 = eccentric_anomaly * eccentric_anomaly;
+        let mut series = c(1.0);
+        for n in (2..=9).rev() {
+            series = c(1.0) - x2 / c((2 * n * (2 * n + 1)) as f64) * series;
+        }
+        let cubic = x2 * eccentric_anomaly / c(6.0) * series;
+        let (one_minus_ec, one_minus_ec_error) = two_sum(c(1.0), -ec);
+        let (linear, linear_error) = two_product(one_minus_ec, eccentric_anomaly);
+        let (cubic, cubic_error) = two_product(ec, cubic);
+        let (sum, sum_error) = two_sum(linear, cubic);
+        let (residual, residual_error) = two_sum(sum, -mean_anomaly);
+        residual + (one_minus_ec_error * eccentric_anomaly + linear_error + cubic_error + sum_error + residual_error)
+    } else {
+        let (product, product_error) = two_product(ec, sin_eccentric_anomaly);
+        let (difference, difference_error) = two_sum(eccentric_anomaly, -product);
+        let (residual, residual_error) = two_sum(difference, -mean_anomaly);
+        residual + (difference_error + residual_error - product_error)
+    }
+}
+
+/// A Halley step rather than a Newton step, since near periapsis at eccentricities close to 1 the
+/// derivative is small enough that a Newton step from the converged estimate can still be off by
+/// tens of ulps
+pub(crate) fn refinement_delta<T: Float>(ec: T, mean_anomaly: T, eccentric_anomaly: T, sin_eccentric_anomaly: T, cos_eccentric_anomaly: T) -> T {
+    let f = kepler_residual(ec, mean_anomaly, eccentric_anomaly, sin_eccentric_anomaly);
+    let f_prime = T::from_f64(1.0) - ec * cos_eccentric_anomaly;
+    let f_prime_prime = ec * sin_eccentric_anomaly;
+    -f / (f_prime - f * f_prime_prime / (T::from_f64(2.0) * f_prime))
+}
+
+/// The sine and cosine of E + `delta` from those of E. The Taylor series of the sine and cosine of
+/// `delta` are summed to the delta^21 and delta^20 terms, which is exact to rounding for |`delta`|
+/// up to about 1
+pub(crate) fn rotate<T: Float>(sin: T, cos: T, delta: T) -> (T, T) {
+    let c = T::from_f64;
+    let delta_squared = delta * delta;
+    let (mut sin_series, mut cos_series) = (c(1.0), c(1.0));
+    for n in (1..=10).rev() {
+        sin_series = c(1.0) - delta_squared / c((2 * n * (2 * n + 1)) as f64) * sin_series;
+        cos_series = c(1.0) - delta_squared / c(((2 * n - 1) * 2 * n) as f64) * cos_series;
+    }
+    let (sin_delta, cos_delta) = (delta * sin_series, cos_series);
+    (sin * cos_delta + cos * sin_delta, cos * cos_delta - sin * sin_delta)
+}
+
+/// Maps an eccentric anomaly in [-pi, pi] to [0, 2pi)
+pub(crate) fn wrap<T: Float>(eccentric_anomaly: T) -> T {
+    if eccentric_anomaly < T::from_f64(0.0) {
+        // Tiny negative anomalies round up to exactly 2pi, which is outside the range
+        let wrapped = eccentric_anomaly + T::TAU;
+        if wrapped < T::TAU { wrapped } else { T::from_f64(0.0) }
+    } else {
         eccentric_anomaly
     }
 }
 
+fn unwrap<T: Float>(eccentric_anomaly: T, revolutions: T) -> T {
+    revolutions.mul_add(T::TAU, revolutions.mul_add(T::TAU_LO, eccentric_anomaly))
+}
+
 #[cfg(test)]
 mod test {
-    use crate::bisection::bisection;
+    use std::f64::consts::TAU;
+
+    use crate::{bisection::bisection, config::{Precision, SolverConfig}, double_double::DoubleDouble, error::SolveError, reference};
 
-    use super::EllipseSolver;
+    use super::{solve_batch, EllipseSolver};
 
     fn solve_with_bisection(e: f64, m: f64) -> f64 {
         // We don't care about speed here, so just use as wide a range as possible
@@ -95,4 +469,249 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_ellipse_terminates() {
+        let solver = EllipseSolver::new(0.5);
+        assert!(solver.solve(f64::NAN).is_nan());
+        assert!(solver.solve(f64::INFINITY).is_nan());
+        EllipseSolver::new(1.5).solve(1.0);
+        EllipseSolver::new(f64::NAN).solve(1.0);
+    }
+
+    #[test]
+    fn test_ellipse_try_solve() {
+        let solver = EllipseSolver::new(0.5);
+        assert_eq!(solver.try_solve(1.0), Ok(solver.solve(1.0)));
+        assert_eq!(solver.try_solve(f64::NAN), Err(SolveError::NonFiniteInput));
+        assert_eq!(solver.try_solve(f64::NEG_INFINITY), Err(SolveError::NonFiniteInput));
+        assert_eq!(EllipseSolver::new(f64::NAN).try_solve(1.0), Err(SolveError::NonFiniteInput));
+        assert_eq!(EllipseSolver::new(1.0).try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: 1.0 }));
+        assert_eq!(EllipseSolver::new(-0.1).try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: -0.1 }));
+    }
+
+    #[test]
+    fn test_ellipse_range_reduction() {
+        for e in [0.0, 0.1, 0.5, 0.9, 0.99] {
+            let solver = EllipseSolver::new(e);
+            for revolutions in -1000..1000 {
+                for x in 0..20 {
+                    let m = revolutions as f64 * TAU + x as f64 * 0.31;
+                    let wrapped = solver.solve(m);
+                    let unwrapped = solver.solve_unwrapped(m);
+                    assert!((0.0..TAU).contains(&wrapped));
+                    let difference = unwrapped - wrapped;
+                    assert!((difference - (difference / TAU).round() * TAU).abs() < 1.0e-9);
+                    let residual = unwrapped - e * unwrapped.sin() - m;
+                    if residual.abs() > 1.0e-9 {
+                        dbg!(e, m, unwrapped, residual);
+                        panic!()
+                    }
+                    assert_eq!(solver.solve_unwrapped(-m), -unwrapped);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ellipse_unwrapped_is_continuous() {
+        let solver = EllipseSolver::new(0.7);
+        let mut previous = solver.solve_unwrapped(-100.0);
+        for x in -99999..100000 {
+            let current = solver.solve_unwrapped(x as f64 / 1000.0);
+            // dE/dM = 1 / (1 - e cos E) <= 1 / (1 - e)
+            assert!(current > previous && current - previous < 1.0e-3 / 0.3 + 1.0e-9);
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn test_ellipse_f32() {
+        for x in 1..99 {
+            let e = x as f32 / 100.0;
+            let solver = EllipseSolver::new(e);
+            for y in 0..628 {
+                let m = y as f32 / 100.0;
+                let expected = solve_with_bisection(e as f64, m as f64);
+                let actual = solver.try_solve(m).unwrap() as f64;
+                let difference = if actual != 0.0 { (expected - actual) / actual } else { expected - actual }.abs();
+                if difference > 1.0e-4 {
+                    dbg!(expected, actual, e, m);
+                    panic!()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ellipse_solve_into() {
+        let mean_anomalies: Vec<f64> = (-1000..1000)
+            .map(|x| x as f64 / 10.0)
+            .collect();
+        let mut out = vec![0.0; mean_anomalies.len()];
+        for e in [0.0, 0.3, 0.9, 0.999] {
+            let solver = EllipseSolver::new(e);
+            solver.solve_into(&mean_anomalies, &mut out);
+            for (m, actual) in mean_anomalies.iter().zip(&out) {
+                assert_eq!(*actual, solver.solve(*m));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ellipse_solve_batch() {
+        let eccentricities: Vec<f64> = (0..1000)
+            .map(|x| x as f64 / 1000.0)
+            .collect();
+        let mean_anomalies: Vec<f64> = (0..1000)
+            .map(|x| x as f64 * 0.37 - 100.0)
+            .collect();
+        let mut out = vec![0.0; mean_anomalies.len()];
+        solve_batch(&eccentricities, &mean_anomalies, &mut out);
+        for ((e, m), actual) in eccentricities.iter().zip(&mean_anomalies).zip(&out) {
+            assert_eq!(*actual, EllipseSolver::new(*e).solve(*m));
+        }
+    }
+
+    #[test]
+    fn test_ellipse_solve_with_trig() {
+        for e in [0.0, 0.3, 0.9, 0.999] {
+            let solver = EllipseSolver::new(e);
+            for x in -1000..1000 {
+                let m = x as f64 / 10.0;
+                let (eccentric_anomaly, sin, cos) = solver.solve_with_trig(m);
+                assert_eq!(eccentric_anomaly, solver.solve(m));
+                assert!((sin - eccentric_anomaly.sin()).abs() < 1.0e-15 && (cos - eccentric_anomaly.cos()).abs() < 1.0e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ellipse_config() {
+        for e in [0.0, 0.3, 0.6, 0.9] {
+            let fast = EllipseSolver::with_config(e, SolverConfig::ellipse(Precision::Fast));
+            let balanced = EllipseSolver::with_config(e, SolverConfig::ellipse(Precision::Balanced));
+            let exact = EllipseSolver::with_config(e, SolverConfig::ellipse(Precision::Exact));
+            let mean_anomalies: Vec<f64> = (-100..100).map(|x| x as f64 / 10.0).collect();
+            let mut out = vec![0.0; mean_anomalies.len()];
+            exact.solve_into(&mean_anomalies, &mut out);
+            for (m, out) in mean_anomalies.iter().zip(out) {
+                let reference = reference::solve_ellipse(e, *m);
+                let error = |actual: f64| (DoubleDouble::from(actual) - reference).abs().hi / reference.abs().hi.max(1.0);
+                assert!(error(fast.solve_unwrapped(*m)) < 2.0e-5);
+                assert_eq!(balanced.solve_unwrapped(*m), EllipseSolver::new(e).solve_unwrapped(*m));
+                assert!(error(exact.solve_unwrapped(*m)) < 4.0 * f64::EPSILON);
+                assert_eq!(out, exact.solve(*m));
+            }
+        }
+        // Near periapsis at eccentricities close to 1 is where an uncompensated residual loses digits
+        for e in [0.9, 0.99, 0.9999, 0.999999, 1.0 - 1.0e-9] {
+            let exact = EllipseSolver::with_config(e, SolverConfig::ellipse(Precision::Exact));
+            for x in 1..200 {
+                let m = 10.0_f64.powf(-x as f64 / 15.0);
+                let error = reference::ulp_error(exact.solve_unwrapped(m), reference::solve_ellipse(e, m));
+                if error > 1.5 {
+                    dbg!(e, m, error);
+                    panic!()
+                }
+            }
+        }
+        let config = SolverConfig { max_iterations: 1, ..SolverConfig::ellipse(Precision::Balanced) };
+        assert_eq!(EllipseSolver::with_config(0.9, config).try_solve(3.0), Err(SolveError::NotConverged { iterations: 1 }));
+    }
+
+    #[test]
+    fn test_ellipse_solve_report() {
+        for e in [0.0, 0.3, 0.9, 0.999] {
+            let solver = EllipseSolver::new(e);
+            for x in -100..100 {
+                let m = x as f64 / 10.0;
+                let report = solver.solve_report(m);
+                assert_eq!(report.anomaly, solver.solve(m));
+                assert!(report.residual.abs() < 1.0e-9);
+                assert!(report.converged && report.iterations >= 1 && report.iterations < 10);
+                assert_eq!(report.branch, None);
+            }
+        }
+        let config = SolverConfig { max_iterations: 1, ..SolverConfig::ellipse(Precision::Balanced) };
+        let report = EllipseSolver::with_config(0.9, config).solve_report(3.0_f64);
+        assert!(!report.converged && report.iterations == 1);
+    }
+
+    #[test]
+    fn test_ellipse_solve_certified() {
+        for e in [0.0, 0.3, 0.9, 0.999] {
+            for x in -100..100 {
+                let m = x as f64 / 10.0;
+                let enclosure = EllipseSolver::new(e).solve_certified(m).unwrap();
+                let reference = reference::solve_ellipse(e, m);
+                assert!((reference - enclosure.lo.into()).hi >= 0.0);
+                assert!((DoubleDouble::from(enclosure.hi) - reference).hi >= 0.0);
+                assert!(enclosure.width() < 1.0e-13 * enclosure.hi.abs().max(1.0));
+            }
+        }
+        assert_eq!(EllipseSolver::new(f64::NAN).solve_certified(1.0), Err(SolveError::NonFiniteInput));
+    }
+
+    #[test]
+    fn test_ellipse_partials() {
+        let h = 1.0e-6;
+        for e in [0.0, 0.3, 0.9] {
+            for x in -100..100 {
+                let m = x as f64 / 10.0;
+                let solve = |e: f64, m: f64| EllipseSolver::new(e).solve_unwrapped(m);
+                let partials = |e: f64, m: f64| EllipseSolver::new(e).solve_with_partials(m).1;
+                let (_, first, second) = EllipseSolver::new(e).solve_with_second_partials(m);
+                assert_eq!(first, partials(e, m));
+                // Compare against central differences, which are only good to about h^2
+                let expected = [
+                    (solve(e, m + h) - solve(e, m - h)) / (2.0 * h),
+                    (solve(e + h, m) - solve(e - h, m)) / (2.0 * h),
+                    (partials(e, m + h).d_mean_anomaly - partials(e, m - h).d_mean_anomaly) / (2.0 * h),
+                    (partials(e + h, m).d_mean_anomaly - partials(e - h, m).d_mean_anomaly) / (2.0 * h),
+                    (partials(e + h, m).d_eccentricity - partials(e - h, m).d_eccentricity) / (2.0 * h),
+                ];
+                let actual = [first.d_mean_anomaly, first.d_eccentricity, second.d2_mean_anomaly, second.d2_mean_anomaly_eccentricity, second.d2_eccentricity];
+                for (expected, actual) in expected.iter().zip(actual) {
+                    if (expected - actual).abs() > 1.0e-4 * expected.abs().max(1.0) {
+                        dbg!(expected, actual, e, m);
+                        panic!()
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ellipse_solve_lanes() {
+        for e in [0.0, 0.3, 0.9, 0.999] {
+            let solver = EllipseSolver::new(e);
+            // The last batch is far enough out that reducing with a three part split of 2pi loses digits
+            for x in -1000..1001 {
+                let mean_anomalies: [f64; 8] = if x == 1000 {
+                    [1.0e7, -3.0e8, 1.0e9, 1.0e10, -1.0e12, 1.0e13, 1.0e15, -1.0e15]
+                } else {
+                    std::array::from_fn(|i| (8 * x + i as i32) as f64 / 100.0)
+                };
+                let x4 = solver.solve_x4(mean_anomalies[..4].try_into().unwrap());
+                let x8 = solver.solve_x8(mean_anomalies);
+                for i in 0..8 {
+                    let expected = solver.solve(mean_anomalies[i]);
+                    // Either side may have wrapped an anomaly right next to 0
+                    let difference = x8[i] - expected;
+                    let difference = (difference - TAU * (difference / TAU).round()).abs();
+                    if !(0.0..TAU).contains(&x8[i]) || difference > 1.0e-9 || (i < 4 && x4[i] != x8[i]) {
+                        dbg!(expected, x8[i], e, mean_anomalies[i]);
+                        panic!()
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_ellipse_solve_into_length_mismatch() {
+        EllipseSolver::new(0.5).solve_into(&[1.0, 2.0], &mut [0.0]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,46 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum SolveError {
+    /// The mean anomaly or eccentricity was NaN or infinite
+    NonFiniteInput,
+    /// The eccentricity is outside the range the solver works for
+    EccentricityOutOfRange { eccentricity: f64 },
+    /// The iteration limit was reached before the delta fell below the threshold
+    NotConverged { iterations: usize },
+    /// The true anomaly is on or beyond the asymptote of a parabolic or hyperbolic orbit, so there's
+    /// no point on the orbit with that anomaly
+    BeyondAsymptote { eccentricity: f64, true_anomaly: f64 },
+    /// A nonzero number of revolutions was asked for on a parabolic or hyperbolic orbit, which the
+    /// body only passes along once
+    RevolutionsOnOpenOrbit { eccentricity: f64, revolutions: usize },
+    /// The state has no angular momentum, so the orbit is a straight line with no orbital plane
+    RadialOrbit,
+    /// The time of flight is zero or negative
+    InvalidTimeOfFlight { time_of_flight: f64 },
+    /// The positions of a transfer are collinear, so the plane of the transfer is undefined, or the
+    /// plane contains the z axis, so the direction of the transfer is ambiguous
+    DegenerateTransfer,
+    /// No interval could be proven to contain the solution, usually because the derivative of
+    /// Kepler's equation is too close to zero near the solution
+    NotCertified,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::NonFiniteInput => write!(f, "input is not finite"),
+            SolveError::EccentricityOutOfRange { eccentricity } => write!(f, "eccentricity {} is out of range for this solver", eccentricity),
+            SolveError::NotConverged { iterations } => write!(f, "solver did not converge after {} iterations", iterations),
+            SolveError::BeyondAsymptote { eccentricity, true_anomaly } => write!(f, "true anomaly {} is beyond the asymptote of an orbit with eccentricity {}", true_anomaly, eccentricity),
+            SolveError::RevolutionsOnOpenOrbit { eccentricity, revolutions } => write!(f, "{} revolutions asked for on an open orbit with eccentricity {}", revolutions, eccentricity),
+            SolveError::RadialOrbit => write!(f, "orbit is radial, with no angular momentum"),
+            SolveError::InvalidTimeOfFlight { time_of_flight } => write!(f, "time of flight {} is not positive", time_of_flight),
+            SolveError::DegenerateTransfer => write!(f, "transfer plane is undefined or contains the z axis"),
+            SolveError::NotCertified => write!(f, "could not certify an interval containing the solution"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
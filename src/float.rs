@@ -0,0 +1,101 @@
+use std::{fmt::{Debug, Display}, ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign}};
+
+/// The floating point operations the solvers need, implemented for `f32` and `f64`. The iteration
+/// thresholds depend on the precision of the type, so they live here too.
+pub trait Float:
+    Copy + Debug + Display + PartialEq + PartialOrd
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+    + AddAssign + SubAssign + MulAssign + DivAssign
+{
+    const PI: Self;
+    const TAU: Self;
+    /// The difference between 2pi and `TAU`, for range reduction
+    const TAU_LO: Self;
+    const EPSILON: Self;
+    const MIN_POSITIVE: Self;
+    /// Threshold on the delta of the elliptic Laguerre iteration
+    const DELTA_THRESHOLD: Self;
+    /// Threshold on the delta of the Halley iteration on the hyperbolic Pade cubic
+    const CUBIC_DELTA_THRESHOLD: Self;
+
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn signum(self) -> Self;
+    fn round(self) -> Self;
+    fn floor(self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn mul_add(self, a: Self, b: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn cbrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+    fn tanh(self) -> Self;
+    fn asinh(self) -> Self;
+    fn acosh(self) -> Self;
+    fn atanh(self) -> Self;
+    fn is_finite(self) -> bool;
+    fn is_nan(self) -> bool;
+}
+
+macro_rules! impl_float {
+    ($t:ident, $tau_lo:expr, $delta_threshold:expr, $cubic_delta_threshold:expr) => {
+        impl Float for $t {
+            const PI: Self = std::$t::consts::PI;
+            const TAU: Self = std::$t::consts::TAU;
+            const TAU_LO: Self = $tau_lo;
+            const EPSILON: Self = $t::EPSILON;
+            const MIN_POSITIVE: Self = $t::MIN_POSITIVE;
+            const DELTA_THRESHOLD: Self = $delta_threshold;
+            const CUBIC_DELTA_THRESHOLD: Self = $cubic_delta_threshold;
+
+            fn from_f64(value: f64) -> Self { value as $t }
+            fn to_f64(self) -> f64 { self as f64 }
+
+            fn abs(self) -> Self { $t::abs(self) }
+            fn signum(self) -> Self { $t::signum(self) }
+            fn round(self) -> Self { $t::round(self) }
+            fn floor(self) -> Self { $t::floor(self) }
+            fn max(self, other: Self) -> Self { $t::max(self, other) }
+            fn min(self, other: Self) -> Self { $t::min(self, other) }
+            fn mul_add(self, a: Self, b: Self) -> Self { $t::mul_add(self, a, b) }
+            fn powi(self, n: i32) -> Self { $t::powi(self, n) }
+            fn powf(self, n: Self) -> Self { $t::powf(self, n) }
+            fn sqrt(self) -> Self { $t::sqrt(self) }
+            fn cbrt(self) -> Self { $t::cbrt(self) }
+            fn exp(self) -> Self { $t::exp(self) }
+            fn ln(self) -> Self { $t::ln(self) }
+            fn sin(self) -> Self { $t::sin(self) }
+            fn cos(self) -> Self { $t::cos(self) }
+            fn tan(self) -> Self { $t::tan(self) }
+            fn asin(self) -> Self { $t::asin(self) }
+            fn acos(self) -> Self { $t::acos(self) }
+            fn atan(self) -> Self { $t::atan(self) }
+            fn atan2(self, other: Self) -> Self { $t::atan2(self, other) }
+            fn sinh(self) -> Self { $t::sinh(self) }
+            fn cosh(self) -> Self { $t::cosh(self) }
+            fn tanh(self) -> Self { $t::tanh(self) }
+            fn asinh(self) -> Self { $t::asinh(self) }
+            fn acosh(self) -> Self { $t::acosh(self) }
+            fn atanh(self) -> Self { $t::atanh(self) }
+            fn is_finite(self) -> bool { $t::is_finite(self) }
+            fn is_nan(self) -> bool { $t::is_nan(self) }
+        }
+    };
+}
+
+impl_float!(f32, -1.748_455_6e-7, 1.0e-6, 1.0e-3);
+impl_float!(f64, 2.449_293_598_294_706_4e-16, 1.0e-10, 1.0e-6);
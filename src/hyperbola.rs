@@ -1,7 +1,7 @@
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 
-const CUBIC_DELTA_THRESHOLD: f64 = 1.0e-6;
+use crate::{config::{Precision, SolverConfig}, double_double::{two_product, two_sum}, dual::{implicit_correction, DualNumber}, error::SolveError, float::Float, interval::{certify, Interval}, partials::{Partials, SecondPartials}, report::{HyperbolaBranch, SolveReport}};
 
 lazy_static! {
     // From eq. 4 in the B. Wu et all paper
@@ -23,7 +23,9 @@ lazy_static! {
         3.0  / 8.0,
         29.0 / 200.0];
 
-    static ref PADE_ORDERS: [f64; 15] = [
+    static ref PADE_ECCENTRIC_ANOMALY_THRESHOLD_SINHS: [f64; 15] = PADE_ECCENTRIC_ANOMALY_THRESHOLDS.map(f64::sinh);
+
+    pub(crate) static ref PADE_ORDERS: [f64; 15] = [
         10.0 / 2.0,
         9.0 / 2.0,
         8.0 / 2.0,
@@ -34,7 +36,7 @@ lazy_static! {
         7.0 / 4.0,
         6.0 / 4.0,
         5.0 / 4.0,
-        4.0 / 4.0,
+        1.0, // 4.0 / 4.0
         3.0 / 4.0,
         2.0 / 4.0,
         1.0 / 4.0,
@@ -45,39 +47,48 @@ fn hyperbolic_kepler_equation(eccentricity: f64, eccentric_anomaly: f64) -> f64
     eccentricity * f64::sinh(eccentric_anomaly) - eccentric_anomaly
 }
 
-fn pade_approximation(ec: f64, mh: f64, a: f64) -> [f64; 4] {
-    let ex = f64::exp(a);
-    let enx = f64::exp(-a);
-    let sa = (ex-enx) / 2.0; // sinh(a)
-    let ca = (ex+enx) / 2.0; // cosh(a)
-    let d1 = ca.powi(2) + 3.0;
-    let d2 = sa.powi(2) + 4.0;
-    let p1 = ca * (3.0 * ca.powi(2) + 17.0) / (5.0 * d1);
-    let p2 = sa * (3.0 * sa.powi(2) + 28.0) / (20.0 * d2);
-    let p3 = ca * (ca.powi(2) + 27.0) / (60.0 * d1);
-    let q1 = -2.0 * ca * sa / (5.0 * d1);
-    let q2 = (sa.powi(2) - 4.0) / (20.0 * d2);
+fn pade_approximation<T: Float>(ec: T, mh: T, a: T) -> [T; 4] {
+    let c = T::from_f64;
+    let ex = T::exp(a);
+    let enx = T::exp(-a);
+    let sa = (ex-enx) / c(2.0); // sinh(a)
+    let ca = (ex+enx) / c(2.0); // cosh(a)
+    pade_coefficients(ec, mh, a, sa, ca)
+}
+
+/// `sa` and `ca` are sinh(a) and cosh(a)
+fn pade_coefficients<T: Float>(ec: T, mh: T, a: T, sa: T, ca: T) -> [T; 4] {
+    let c = T::from_f64;
+    let d1 = ca.powi(2) + c(3.0);
+    let d2 = sa.powi(2) + c(4.0);
+    let p1 = ca * (c(3.0) * ca.powi(2) + c(17.0)) / (c(5.0) * d1);
+    let p2 = sa * (c(3.0) * sa.powi(2) + c(28.0)) / (c(20.0) * d2);
+    let p3 = ca * (ca.powi(2) + c(27.0)) / (c(60.0) * d1);
+    let q1 = c(-2.0) * ca * sa / (c(5.0) * d1);
+    let q2 = (sa.powi(2) - c(4.0)) / (c(20.0) * d2);
     let coefficient_f3 = ec * p3 - q2;
     let coefficient_f2 = ec * p2 - (mh + a) * q2 - q1;
-    let coefficient_f1 = ec * p1 - (mh + a) * q1 - 1.0;
+    let coefficient_f1 = ec * p1 - (mh + a) * q1 - c(1.0);
     let coefficient_f0 = ec * sa - mh - a;
     [coefficient_f3,coefficient_f2, coefficient_f1, coefficient_f0]
 }
 
-fn solve_cubic(coefficients: [f64; 4], mh: f64, ec: f64) -> f64 {
-    let mut x = mh / (ec - 1.0); // starting value from series expansion of HKE
-    loop {
+/// Returns the root, the number of iterations and whether the iteration converged
+fn solve_cubic<T: Float>(coefficients: [T; 4], mh: T, ec: T, config: &SolverConfig<T>) -> (T, usize, bool) {
+    let c = T::from_f64;
+    let mut x = mh / (ec - c(1.0)); // starting value from series expansion of HKE
+    for iteration in 1..=config.max_iterations {
         // halley's method
         let f = ((coefficients[0]*x + coefficients[1])*x + coefficients[2])*x + coefficients[3];
-        let f_prime = (3.0*coefficients[0]*x + 2.0*coefficients[1])*x + coefficients[2];
-        let f_prime_prime = 6.0*coefficients[0]*x + 2.0*coefficients[1];
-        let delta = -2.0*f*f_prime / (2.0*f_prime.powi(2) - f*f_prime_prime);
-        if delta.abs() < CUBIC_DELTA_THRESHOLD {
-            break;
+        let f_prime = (c(3.0)*coefficients[0]*x + c(2.0)*coefficients[1])*x + coefficients[2];
+        let f_prime_prime = c(6.0)*coefficients[0]*x + c(2.0)*coefficients[1];
+        let delta = c(-2.0)*f*f_prime / (c(2.0)*f_prime.powi(2) - f*f_prime_prime);
+        if config.converged(delta, x) {
+            return (x, iteration, true);
         }
         x += delta;
     }
-    x
+    (x, config.max_iterations, false)
 }
 
 /// ## Example
@@ -92,64 +103,296 @@ fn solve_cubic(coefficients: [f64; 4], mh: f64, ec: f64) -> f64 {
 /// }
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HyperbolaSolver {
-    eccentricity: f64,
-    pade_mean_anomaly_thresholds: [f64; 15],
+pub struct HyperbolaSolver<T = f64> {
+    eccentricity: T,
+    pub(crate) pade_mean_anomaly_thresholds: [T; 15],
+    pub(crate) config: SolverConfig<T>,
 }
 
-impl HyperbolaSolver {
-    pub fn new(eccentricity: f64) -> Self {
-        let pade_mean_anomaly_thresholds = PADE_ECCENTRIC_ANOMALY_THRESHOLDS.map(|eccentric_anomaly| hyperbolic_kepler_equation(eccentricity, eccentric_anomaly));
-        Self { eccentricity, pade_mean_anomaly_thresholds }
+impl<T: Float> HyperbolaSolver<T> {
+    pub fn new(eccentricity: T) -> Self {
+        Self::with_config(eccentricity, SolverConfig::hyperbola(Precision::Balanced))
+    }
+
+    pub fn with_config(eccentricity: T, config: SolverConfig<T>) -> Self {
+        // The thresholds are computed in f64 regardless of T, since this only happens once
+        let pade_mean_anomaly_thresholds = PADE_ECCENTRIC_ANOMALY_THRESHOLDS
+            .map(|eccentric_anomaly| T::from_f64(hyperbolic_kepler_equation(eccentricity.to_f64(), eccentric_anomaly)));
+        Self { eccentricity, pade_mean_anomaly_thresholds, config }
+    }
+
+    /// Works with all values of mean anomaly 0 to infinity, and always terminates
+    pub fn solve(&self, mean_anomaly: T) -> T {
+        self.iterate(mean_anomaly).0
+    }
+
+    /// Same as `solve`, but also returns the hyperbolic sine and cosine of the hyperbolic anomaly.
+    /// These are carried through the final Halley step from the values it already computes, rather
+    /// than computed from scratch
+    pub fn solve_with_trig(&self, mean_anomaly: T) -> (T, T, T) {
+        let mh = mean_anomaly.abs();
+        let (f0, _, _, _) = starter(self.eccentricity, mh, |i| self.pade_mean_anomaly_thresholds[i], &self.config);
+        let (hyperbolic_anomaly, sinh, cosh) = match self.config.refinement_steps {
+            0 => (f0, f0.sinh(), f0.cosh()),
+            steps => halley_step_with_trig(self.eccentricity, mh, refine(self.eccentricity, mh, f0, steps - 1)),
+        };
+        let sign = mean_anomaly.signum();
+        (hyperbolic_anomaly * sign, sinh * sign, cosh)
+    }
+
+    /// Solves for the hyperbolic anomaly with a dual number eccentricity and mean anomaly, with the
+    /// derivative parts filled in by implicitly differentiating Kepler's equation. The real part of
+    /// `eccentricity` has to be the solver's eccentricity
+    pub fn solve_dual<D: DualNumber<T>>(&self, eccentricity: D, mean_anomaly: D) -> D {
+        debug_assert!(eccentricity.real() == self.eccentricity, "the real part of eccentricity must be the solver's eccentricity");
+        let root = self.solve(mean_anomaly.real());
+        implicit_correction(root, |hyperbolic_anomaly: D| {
+            let f = eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly - mean_anomaly;
+            let f_prime = eccentricity * hyperbolic_anomaly.cosh() - D::from_real(T::from_f64(1.0));
+            (f, f_prime)
+        })
+    }
+
+    /// Same as `solve`, but also returns the derivatives of the hyperbolic anomaly with respect to
+    /// the mean anomaly and eccentricity
+    pub fn solve_with_partials(&self, mean_anomaly: T) -> (T, Partials<T>) {
+        let (hyperbolic_anomaly, sinh, cosh) = self.solve_with_trig(mean_anomaly);
+        // Differentiating e sinh(F) - F = M gives (e cosh(F) - 1) dF = dM - sinh(F) de
+        let derivative = self.eccentricity * cosh - T::from_f64(1.0);
+        (hyperbolic_anomaly, Partials { d_mean_anomaly: T::from_f64(1.0) / derivative, d_eccentricity: -sinh / derivative })
+    }
+
+    /// Same as `solve_with_partials`, but also returns the second derivatives
+    pub fn solve_with_second_partials(&self, mean_anomaly: T) -> (T, Partials<T>, SecondPartials<T>) {
+        let (hyperbolic_anomaly, sinh, cosh) = self.solve_with_trig(mean_anomaly);
+        let ec = self.eccentricity;
+        let derivative = ec * cosh - T::from_f64(1.0);
+        let derivative3 = derivative.powi(3);
+        let partials = Partials { d_mean_anomaly: T::from_f64(1.0) / derivative, d_eccentricity: -sinh / derivative };
+        let second_partials = SecondPartials {
+            d2_mean_anomaly: -ec * sinh / derivative3,
+            d2_mean_anomaly_eccentricity: (cosh - ec) / derivative3,
+            d2_eccentricity: (T::from_f64(2.0) * sinh * cosh * derivative - ec * sinh.powi(3)) / derivative3,
+        };
+        (hyperbolic_anomaly, partials, second_partials)
     }
 
-    /// Works with all values of mean anomaly 0 to infinity
-    pub fn solve(&self, mean_anomaly: f64) -> f64 {
+    /// Same as `solve`, but also reports the residual, the number of iterations and which initial
+    /// approximation was used, for diagnosing bad results
+    pub fn solve_report(&self, mean_anomaly: T) -> SolveReport<T> {
+        let mh = mean_anomaly.abs();
+        let (f0, branch, iterations, converged) = starter(self.eccentricity, mh, |i| self.pade_mean_anomaly_thresholds[i], &self.config);
+        let hyperbolic_anomaly = refine(self.eccentricity, mh, f0, self.config.refinement_steps);
+        let sign = mean_anomaly.signum();
+        let residual = (self.eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly - mh) * sign;
+        SolveReport {
+            anomaly: hyperbolic_anomaly * sign,
+            residual,
+            iterations: iterations + self.config.refinement_steps,
+            converged,
+            branch: Some(branch),
+        }
+    }
+
+    /// Works with all values of mean anomaly 0 to infinity, returns an error rather than a garbage
+    /// value if the input is invalid or the iteration fails to converge
+    pub fn try_solve(&self, mean_anomaly: T) -> Result<T, SolveError> {
+        if !mean_anomaly.is_finite() || !self.eccentricity.is_finite() {
+            return Err(SolveError::NonFiniteInput);
+        }
+        if self.eccentricity <= T::from_f64(1.0) {
+            return Err(SolveError::EccentricityOutOfRange { eccentricity: self.eccentricity.to_f64() });
+        }
+        match self.iterate(mean_anomaly) {
+            (eccentric_anomaly, true) => Ok(eccentric_anomaly),
+            (_, false) => Err(SolveError::NotConverged { iterations: self.config.max_iterations }),
+        }
+    }
+
+    /// Returns the hyperbolic anomaly and whether the iteration converged
+    fn iterate(&self, mean_anomaly: T) -> (T, bool) {
         // Solver assumes mean anomaly > 0
         // The equation is symmetric, so for mean anomaly < 0, we just flip the sign o the output
-        let ec = self.eccentricity;
         let mh = mean_anomaly.abs();
+        let (f0, _, _, converged) = starter(self.eccentricity, mh, |i| self.pade_mean_anomaly_thresholds[i], &self.config);
+        (refine(self.eccentricity, mh, f0, self.config.refinement_steps) * mean_anomaly.signum(), converged)
+    }
 
-        let f0 = if mh <= self.pade_mean_anomaly_thresholds[0] {
-            // For mh < 5 we use a 'piecewise pade approximation' to get the starting estimate
-            let mut i = 0;
-            while i < self.pade_mean_anomaly_thresholds.len()-1 && mh < self.pade_mean_anomaly_thresholds[i+1] {
-                i += 1;
-            }
-            let a = PADE_ORDERS[i];
-            let coefficients = pade_approximation(ec, mh, a);
-
-            solve_cubic(coefficients, mh, ec) + a
-
-        } else {
-            // For mh >= 5, we can use this... thing that I copied from the above paper
-            // I have no idea how it works, but it works very very very well
-            let fa = f64::ln(2.0 * mh / ec);
-            let ca = 0.5 * (2.0 * mh / ec + ec / (2.0 * mh));
-            let sa = 0.5 * (2.0 * mh / ec - ec / (2.0 * mh));
-            let top = 6.0 * (ec.powi(2) / (4.0 * mh) + fa) / (ec * ca - 1.0) 
-                + 3.0 * (ec * sa / (ec * ca - 1.0)) * ((ec.powi(2) / (4.0 * mh) + fa) / (ec * ca - 1.0)).powi(2);
-            let bottom = 6.0 + 6.0 * (ec * sa / (ec * ca - 1.0)) * ((ec.powi(2) / (4.0 * mh) + fa) / (ec * ca - 1.0))
-                + (ec * ca / (ec * ca - 1.0)) * ((ec.powi(2) / (4.0 * mh) + fa) / (ec * ca - 1.0)).powi(2);
-            let delta = top / bottom;
-            fa + delta
-        };
+    /// Solves every element of `mean_anomalies` into the same index of `out`. Gives exactly the
+    /// same results as calling `solve` on each element, but does the branchy initial approximation
+    /// and the Halley step as separate passes, so that the compiler can vectorize the second
+    /// # Panics
+    /// If the slices have different lengths
+    pub fn solve_into(&self, mean_anomalies: &[T], out: &mut [T]) {
+        assert_eq!(mean_anomalies.len(), out.len(), "mean_anomalies and out must be the same length");
+        for (mean_anomaly, out) in mean_anomalies.iter().zip(out.iter_mut()) {
+            *out = starter(self.eccentricity, mean_anomaly.abs(), |i| self.pade_mean_anomaly_thresholds[i], &self.config).0;
+        }
+        for (mean_anomaly, out) in mean_anomalies.iter().zip(out.iter_mut()) {
+            *out = refine(self.eccentricity, mean_anomaly.abs(), *out, self.config.refinement_steps) * mean_anomaly.signum();
+        }
+    }
+}
+
+impl HyperbolaSolver<f64> {
+    /// Returns an interval guaranteed to contain the exact solution of Kepler's equation for the
+    /// given eccentricity and mean anomaly, accounting for rounding, or `NotCertified` if the
+    /// interval Newton method couldn't prove one. The interval is usually a few ulps wide
+    pub fn solve_certified(&self, mean_anomaly: f64) -> Result<Interval, SolveError> {
+        let estimate = self.try_solve(mean_anomaly)?;
+        let ec = Interval::point(self.eccentricity);
+        let mean_anomaly = Interval::point(mean_anomaly);
+        certify(
+            estimate,
+            |hyperbolic_anomaly| ec * hyperbolic_anomaly.sinh_cosh().0 - hyperbolic_anomaly - mean_anomaly,
+            |hyperbolic_anomaly| ec * hyperbolic_anomaly.sinh_cosh().1 - Interval::point(1.0),
+        )
+    }
+
+    /// Solves 4 mean anomalies at once. With the `simd` feature on x86_64 this runs the Pade cubic
+    /// and the Halley steps on SSE2 or AVX vectors, with polynomial sinh and cosh, and agrees with
+    /// `solve` to within a few ulp. Otherwise it calls `solve` on each lane
+    pub fn solve_x4(&self, mean_anomalies: [f64; 4]) -> [f64; 4] {
+        self.solve_lanes(mean_anomalies)
+    }
+
+    /// Same as `solve_x4`, but with 8 lanes
+    pub fn solve_x8(&self, mean_anomalies: [f64; 8]) -> [f64; 8] {
+        self.solve_lanes(mean_anomalies)
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fn solve_lanes<const N: usize>(&self, mean_anomalies: [f64; N]) -> [f64; N] {
+        crate::simd::solve_hyperbola(self.eccentricity, &self.pade_mean_anomaly_thresholds, mean_anomalies, &self.config)
+    }
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    fn solve_lanes<const N: usize>(&self, mean_anomalies: [f64; N]) -> [f64; N] {
+        mean_anomalies.map(|mean_anomaly| self.solve(mean_anomaly))
+    }
+}
+
+/// Solves each pair of `eccentricities` and `mean_anomalies` into the same index of `out`. Gives
+/// exactly the same results as `HyperbolaSolver::new(eccentricity).solve(mean_anomaly)` on each
+/// pair, but only computes the Pade thresholds that are actually needed, and does the Halley step
+/// as a separate pass so that the compiler can vectorize it
+/// # Panics
+/// If the slices have different lengths
+pub fn solve_batch<T: Float>(eccentricities: &[T], mean_anomalies: &[T], out: &mut [T]) {
+    assert_eq!(eccentricities.len(), mean_anomalies.len(), "eccentricities and mean_anomalies must be the same length");
+    assert_eq!(mean_anomalies.len(), out.len(), "mean_anomalies and out must be the same length");
+    let config = SolverConfig::hyperbola(Precision::Balanced);
+    for ((eccentricity, mean_anomaly), out) in eccentricities.iter().zip(mean_anomalies).zip(out.iter_mut()) {
+        // Same as hyperbolic_kepler_equation, but with sinh of the thresholds looked up rather than computed
+        let threshold = |i: usize| T::from_f64(eccentricity.to_f64() * PADE_ECCENTRIC_ANOMALY_THRESHOLD_SINHS[i] - PADE_ECCENTRIC_ANOMALY_THRESHOLDS[i]);
+        *out = starter(*eccentricity, mean_anomaly.abs(), threshold, &config).0;
+    }
+    for ((eccentricity, mean_anomaly), out) in eccentricities.iter().zip(mean_anomalies).zip(out.iter_mut()) {
+        *out = refine(*eccentricity, mean_anomaly.abs(), *out, config.refinement_steps) * mean_anomaly.signum();
+    }
+}
 
-        // Halley method
-        let f = ec * f0.sinh() - f0 - mh;
-        let f_prime = ec * f0.cosh() - 1.0;
-        let f_prime_prime = f_prime + 1.0;
-        let f1 = f0 - (2.0 * f / f_prime) / (2.0 - f * f_prime_prime / f_prime.powi(2));
+/// Returns the initial approximation for `mh` >= 0, the branch it came from, and the number of
+/// iterations on the Pade cubic and whether they converged. `pade_mean_anomaly_threshold(i)` is the
+/// mean anomaly at `PADE_ECCENTRIC_ANOMALY_THRESHOLDS[i]`
+fn starter<T: Float>(ec: T, mh: T, pade_mean_anomaly_threshold: impl Fn(usize) -> T, config: &SolverConfig<T>) -> (T, HyperbolaBranch, usize, bool) {
+    let c = T::from_f64;
+    if mh <= pade_mean_anomaly_threshold(0) {
+        // For mh < 5 we use a 'piecewise pade approximation' to get the starting estimate
+        let mut i = 0;
+        while i < PADE_ECCENTRIC_ANOMALY_THRESHOLDS.len()-1 && mh < pade_mean_anomaly_threshold(i+1) {
+            i += 1;
+        }
+        let a = c(PADE_ORDERS[i]);
+        let coefficients = pade_approximation(ec, mh, a);
+
+        let (x, iterations, converged) = solve_cubic(coefficients, mh, ec, config);
+        (x + a, HyperbolaBranch::Pade { interval: i }, iterations, converged)
+
+    } else {
+        // For mh >= 5, we can use this... thing that I copied from the above paper
+        // I have no idea how it works, but it works very very very well
+        let fa = T::ln(c(2.0) * mh / ec);
+        let ca = c(0.5) * (c(2.0) * mh / ec + ec / (c(2.0) * mh));
+        let sa = c(0.5) * (c(2.0) * mh / ec - ec / (c(2.0) * mh));
+        let top = c(6.0) * (ec.powi(2) / (c(4.0) * mh) + fa) / (ec * ca - c(1.0)) 
+            + c(3.0) * (ec * sa / (ec * ca - c(1.0))) * ((ec.powi(2) / (c(4.0) * mh) + fa) / (ec * ca - c(1.0))).powi(2);
+        let bottom = c(6.0) + c(6.0) * (ec * sa / (ec * ca - c(1.0))) * ((ec.powi(2) / (c(4.0) * mh) + fa) / (ec * ca - c(1.0)))
+            + (ec * ca / (ec * ca - c(1.0))) * ((ec.powi(2) / (c(4.0) * mh) + fa) / (ec * ca - c(1.0))).powi(2);
+        let delta = top / bottom;
+        (fa + delta, HyperbolaBranch::Analytic, 0, true)
+    }
+}
+
+/// Takes `steps` Halley steps from the initial approximation `f0`
+fn refine<T: Float>(ec: T, mh: T, f0: T, steps: usize) -> T {
+    (0..steps).fold(f0, |f, _| halley_step(ec, mh, f))
+}
+
+fn halley_step<T: Float>(ec: T, mh: T, f0: T) -> T {
+    f0 - halley_delta(ec, mh, f0, f0.sinh(), f0.cosh())
+}
 
-        f1 * mean_anomaly.signum()
+/// Same as `halley_step`, but also returns sinh and cosh of the result
+fn halley_step_with_trig<T: Float>(ec: T, mh: T, f0: T) -> (T, T, T) {
+    let c = T::from_f64;
+    let sinh_f0 = f0.sinh();
+    let cosh_f0 = f0.cosh();
+    let delta = halley_delta(ec, mh, f0, sinh_f0, cosh_f0);
+    let f1 = f0 - delta;
+    if delta.abs() < c(1.0e-3) {
+        // The starter is accurate enough that the step is always tiny, so the addition formulas
+        // only need a few terms of the series for sinh(delta) and cosh(delta)
+        let d2 = delta * delta;
+        let sinh_delta = delta * (c(1.0) + d2 / c(6.0) * (c(1.0) + d2 / c(20.0)));
+        let cosh_delta = c(1.0) + d2 / c(2.0) * (c(1.0) + d2 / c(12.0) * (c(1.0) + d2 / c(30.0)));
+        (f1, sinh_f0 * cosh_delta - cosh_f0 * sinh_delta, cosh_f0 * cosh_delta - sinh_f0 * sinh_delta)
+    } else {
+        (f1, f1.sinh(), f1.cosh())
     }
 }
 
+/// e sinh(F) - F - M, with the rounding errors of the products and sums compensated. For |F| < 1
+/// the equation is rearranged to (e - 1) F + e (sinh(F) - F) - M, with sinh(F) - F summed as a
+/// series, since near periapsis at eccentricities close to 1 e sinh(F) and F cancel and the rounding
+/// error in sinh(F) alone would be many ulps of M
+pub(crate) fn hyperbolic_kepler_residual<T: Float>(ec: T, mh: T, hyperbolic_anomaly: T, sinh_hyperbolic_anomaly: T) -> T {
+    let c = T::from_f64;
+    if hyperbolic_anomaly.abs() < c(1.0) {
+        // sinh(F) - F = F^3 / 3! + F^5 / 5! + ..., to the F^19 term
+        let x2 = hyperbolic_anomaly * hyperbolic_anomaly;
+        let mut series = c(1.0);
+        for n in (2..=9).rev() {
+            series = c(1.0) + x2 / c((2 * n * (2 * n + 1)) as f64) * series;
+        }
+        let cubic = x2 * hyperbolic_anomaly / c(6.0) * series;
+        let (ec_minus_one, ec_minus_one_error) = two_sum(ec, c(-1.0));
+        let (linear, linear_error) = two_product(ec_minus_one, hyperbolic_anomaly);
+        let (cubic, cubic_error) = two_product(ec, cubic);
+        let (sum, sum_error) = two_sum(linear, cubic);
+        let (residual, residual_error) = two_sum(sum, -mh);
+        residual + (ec_minus_one_error * hyperbolic_anomaly + linear_error + cubic_error + sum_error + residual_error)
+    } else {
+        let (product, product_error) = two_product(ec, sinh_hyperbolic_anomaly);
+        let (difference, difference_error) = two_sum(product, -hyperbolic_anomaly);
+        let (residual, residual_error) = two_sum(difference, -mh);
+        residual + (product_error + difference_error + residual_error)
+    }
+}
+
+fn halley_delta<T: Float>(ec: T, mh: T, f0: T, sinh_f0: T, cosh_f0: T) -> T {
+    let c = T::from_f64;
+    let f = hyperbolic_kepler_residual(ec, mh, f0, sinh_f0);
+    let f_prime = ec * cosh_f0 - c(1.0);
+    let f_prime_prime = ec * sinh_f0;
+    (c(2.0) * f / f_prime) / (c(2.0) - f * f_prime_prime / f_prime.powi(2))
+}
+
 #[cfg(test)]
 mod test {
-    use crate::bisection::bisection;
+    use crate::{bisection::bisection, config::{Precision, SolverConfig}, double_double::DoubleDouble, error::SolveError, reference, report::HyperbolaBranch};
 
-    use super::HyperbolaSolver;
+    use super::{solve_batch, HyperbolaSolver};
 
     fn solve_with_bisection(e: f64, m: f64) -> f64 {
         // We don't care about speed here, so just use as wide a range as possible
@@ -179,4 +422,219 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_hyperbola_terminates() {
+        let solver = HyperbolaSolver::new(1.5);
+        assert!(solver.solve(f64::NAN).is_nan());
+        HyperbolaSolver::new(0.5).solve(1.0);
+        HyperbolaSolver::new(f64::NAN).solve(1.0);
+    }
+
+    #[test]
+    fn test_hyperbola_try_solve() {
+        let solver = HyperbolaSolver::new(1.5);
+        assert_eq!(solver.try_solve(1.0), Ok(solver.solve(1.0)));
+        assert_eq!(solver.try_solve(-1.0), Ok(solver.solve(-1.0)));
+        assert_eq!(solver.try_solve(f64::NAN), Err(SolveError::NonFiniteInput));
+        assert_eq!(solver.try_solve(f64::INFINITY), Err(SolveError::NonFiniteInput));
+        assert_eq!(HyperbolaSolver::new(f64::INFINITY).try_solve(1.0), Err(SolveError::NonFiniteInput));
+        assert_eq!(HyperbolaSolver::new(1.0).try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: 1.0 }));
+        assert_eq!(HyperbolaSolver::new(0.5).try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: 0.5 }));
+    }
+
+    #[test]
+    fn test_hyperbola_f32() {
+        for x in 1..100 {
+            let e = 1.0 + f32::powi(x as f32, 2) / 100.0;
+            let solver = HyperbolaSolver::new(e);
+            for y in 0..1000 {
+                let m = f32::powi(y as f32, 2) / 100.0;
+                let expected = solve_with_bisection(e as f64, m as f64);
+                let actual = solver.try_solve(m).unwrap() as f64;
+                let difference = if actual.abs() < 1.0e-5 { expected - actual } else { (expected - actual) / actual }.abs();
+                if difference > 1.0e-4 {
+                    dbg!(expected, actual, difference, e, m);
+                    panic!()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hyperbola_solve_into() {
+        let mean_anomalies: Vec<f64> = (-1000..1000)
+            .map(|x| f64::powi(x as f64, 3) / 10000.0)
+            .collect();
+        let mut out = vec![0.0; mean_anomalies.len()];
+        for e in [1.001, 1.3, 2.0, 100.0] {
+            let solver = HyperbolaSolver::new(e);
+            solver.solve_into(&mean_anomalies, &mut out);
+            for (m, actual) in mean_anomalies.iter().zip(&out) {
+                assert_eq!(*actual, solver.solve(*m));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hyperbola_solve_batch() {
+        let eccentricities: Vec<f64> = (1..1000)
+            .map(|x| 1.0 + f64::powi(x as f64, 2) / 10000.0)
+            .collect();
+        let mean_anomalies: Vec<f64> = (1..1000)
+            .map(|x| f64::powi(x as f64, 2) / 1000.0 - 100.0)
+            .collect();
+        let mut out = vec![0.0; mean_anomalies.len()];
+        solve_batch(&eccentricities, &mean_anomalies, &mut out);
+        for ((e, m), actual) in eccentricities.iter().zip(&mean_anomalies).zip(&out) {
+            assert_eq!(*actual, HyperbolaSolver::new(*e).solve(*m));
+        }
+    }
+
+    #[test]
+    fn test_hyperbola_solve_with_trig() {
+        for e in [1.001, 1.3, 2.0, 100.0] {
+            let solver = HyperbolaSolver::new(e);
+            for x in -1000..1000 {
+                let m = f64::powi(x as f64, 3) / 10000.0;
+                let (hyperbolic_anomaly, sinh, cosh) = solver.solve_with_trig(m);
+                assert_eq!(hyperbolic_anomaly, solver.solve(m));
+                let sinh_difference = (sinh - hyperbolic_anomaly.sinh()).abs() / hyperbolic_anomaly.sinh().abs().max(1.0e-300);
+                let cosh_difference = (cosh - hyperbolic_anomaly.cosh()).abs() / hyperbolic_anomaly.cosh();
+                if sinh_difference > 1.0e-14 || cosh_difference > 1.0e-14 {
+                    dbg!(sinh_difference, cosh_difference, e, m);
+                    panic!()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hyperbola_config() {
+        for e in [1.1, 2.0, 10.0] {
+            let fast = HyperbolaSolver::with_config(e, SolverConfig::hyperbola(Precision::Fast));
+            let balanced = HyperbolaSolver::with_config(e, SolverConfig::hyperbola(Precision::Balanced));
+            let exact = HyperbolaSolver::with_config(e, SolverConfig::hyperbola(Precision::Exact));
+            let mean_anomalies: Vec<f64> = (-100..100).map(|x| f64::powi(x as f64, 3) / 10000.0).collect();
+            let mut out = vec![0.0; mean_anomalies.len()];
+            exact.solve_into(&mean_anomalies, &mut out);
+            for (m, out) in mean_anomalies.iter().zip(out) {
+                let reference = reference::solve_hyperbola(e, *m);
+                let error = |actual: f64| (DoubleDouble::from(actual) - reference).abs().hi / reference.abs().hi.max(1.0);
+                assert!(error(fast.solve(*m)) < 2.0e-5);
+                assert_eq!(balanced.solve(*m), HyperbolaSolver::new(e).solve(*m));
+                assert!(error(exact.solve(*m)) < 4.0 * f64::EPSILON);
+                assert_eq!(out, exact.solve(*m));
+            }
+        }
+        // Near periapsis at eccentricities close to 1 is where an uncompensated residual loses digits
+        for e in [1.01, 1.0001, 1.000001, 1.0 + 1.0e-9] {
+            let exact = HyperbolaSolver::with_config(e, SolverConfig::hyperbola(Precision::Exact));
+            for x in 1..200 {
+                let m = 10.0_f64.powf(-x as f64 / 15.0);
+                let error = reference::ulp_error(exact.solve(m), reference::solve_hyperbola(e, m));
+                if error > 2.0 {
+                    dbg!(e, m, error);
+                    panic!()
+                }
+            }
+        }
+        let config = SolverConfig { max_iterations: 1, ..SolverConfig::hyperbola(Precision::Balanced) };
+        assert_eq!(HyperbolaSolver::with_config(1.1, config).try_solve(3.0), Err(SolveError::NotConverged { iterations: 1 }));
+    }
+
+    #[test]
+    fn test_hyperbola_solve_report() {
+        for e in [1.001, 1.1, 2.0, 10.0] {
+            let solver = HyperbolaSolver::new(e);
+            let analytic_threshold = e * f64::sinh(5.0) - 5.0;
+            let mut last_interval = usize::MAX;
+            for x in 0..200 {
+                let m = f64::powi(x as f64, 3) / 10000.0;
+                let report = solver.solve_report(m);
+                assert_eq!(report.anomaly, solver.solve(m));
+                assert!(report.residual.abs() < 1.0e-10 * m.max(1.0));
+                assert!(report.converged && report.iterations >= 1);
+                assert_eq!(solver.solve_report(-m).anomaly, -report.anomaly);
+                match report.branch {
+                    Some(HyperbolaBranch::Pade { interval }) => {
+                        assert!(m <= analytic_threshold && interval <= last_interval);
+                        last_interval = interval;
+                    }
+                    Some(HyperbolaBranch::Analytic) => assert!(m > analytic_threshold && report.iterations == 1),
+                    None => panic!(),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hyperbola_solve_certified() {
+        for e in [1.001, 1.1, 2.0, 10.0] {
+            for x in -100..100 {
+                let m = f64::powi(x as f64, 3) / 10000.0;
+                let enclosure = HyperbolaSolver::new(e).solve_certified(m).unwrap();
+                let reference = reference::solve_hyperbola(e, m);
+                assert!((reference - enclosure.lo.into()).hi >= 0.0);
+                assert!((DoubleDouble::from(enclosure.hi) - reference).hi >= 0.0);
+                assert!(enclosure.width() < 1.0e-13 * enclosure.hi.abs().max(1.0));
+            }
+        }
+        assert_eq!(HyperbolaSolver::new(f64::NAN).solve_certified(1.0), Err(SolveError::NonFiniteInput));
+    }
+
+    #[test]
+    fn test_hyperbola_partials() {
+        let h = 1.0e-6;
+        for e in [1.1, 2.0, 10.0] {
+            for x in -100..100 {
+                let m = f64::powi(x as f64, 3) / 10000.0;
+                let solve = |e: f64, m: f64| HyperbolaSolver::new(e).solve(m);
+                let partials = |e: f64, m: f64| HyperbolaSolver::new(e).solve_with_partials(m).1;
+                let (_, first, second) = HyperbolaSolver::new(e).solve_with_second_partials(m);
+                assert_eq!(first, partials(e, m));
+                // Compare against central differences, which are only good to about h^2
+                let expected = [
+                    (solve(e, m + h) - solve(e, m - h)) / (2.0 * h),
+                    (solve(e + h, m) - solve(e - h, m)) / (2.0 * h),
+                    (partials(e, m + h).d_mean_anomaly - partials(e, m - h).d_mean_anomaly) / (2.0 * h),
+                    (partials(e + h, m).d_mean_anomaly - partials(e - h, m).d_mean_anomaly) / (2.0 * h),
+                    (partials(e + h, m).d_eccentricity - partials(e - h, m).d_eccentricity) / (2.0 * h),
+                ];
+                let actual = [first.d_mean_anomaly, first.d_eccentricity, second.d2_mean_anomaly, second.d2_mean_anomaly_eccentricity, second.d2_eccentricity];
+                for (expected, actual) in expected.iter().zip(actual) {
+                    if (expected - actual).abs() > 1.0e-4 * expected.abs().max(1.0) {
+                        dbg!(expected, actual, e, m);
+                        panic!()
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hyperbola_solve_lanes() {
+        for e in [1.001, 1.3, 2.0, 100.0] {
+            let solver = HyperbolaSolver::new(e);
+            for x in -1000..1000 {
+                let mean_anomalies: [f64; 8] = std::array::from_fn(|i| f64::powi((8 * x + i as i32) as f64, 3) / 1.0e6);
+                let x4 = solver.solve_x4(mean_anomalies[..4].try_into().unwrap());
+                let x8 = solver.solve_x8(mean_anomalies);
+                for i in 0..8 {
+                    let expected = solver.solve(mean_anomalies[i]);
+                    let difference = (x8[i] - expected).abs() / expected.abs().max(1.0);
+                    if difference > 1.0e-10 || (i < 4 && x4[i] != x8[i]) {
+                        dbg!(expected, x8[i], e, mean_anomalies[i]);
+                        panic!()
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hyperbola_solve_batch_length_mismatch() {
+        solve_batch(&[1.5], &[1.0, 2.0], &mut [0.0, 0.0]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,247 @@
+//! Interval arithmetic for certifying solutions of Kepler's equation. Every operation rounds its
+//! bounds outward by an ulp, which is enough to contain the true result since the hardware rounds
+//! to nearest. The platform's sin, cos, sinh and cosh aren't guaranteed to be correctly rounded,
+//! so their results are widened by `ELEMENTARY_ULPS`; every common libm is well within this.
+//!
+//! Certification uses the interval Newton method: if N(X) = m - f(m) / f'(X), where m is the
+//! midpoint of X, lies strictly inside X, then X contains exactly one root and the root is in N(X).
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SolveError;
+
+/// How many ulps the platform's elementary functions are assumed to be accurate to
+const ELEMENTARY_ULPS: f64 = 4.0;
+/// Initial half-width of the interval around the solver's estimate, relative to the estimate, which
+/// is a little looser than the solvers' thresholds
+const INITIAL_RADIUS: f64 = 1.0e-9;
+/// The half-width is multiplied by this on every attempt that fails to certify
+const RADIUS_GROWTH: f64 = 1.0e3;
+const CERTIFY_ATTEMPTS: usize = 4;
+/// Maximum number of Newton contractions once a root is certified
+const MAX_CONTRACTIONS: usize = 10;
+
+/// The next f64 above `x`, the same as `f64::next_up`, which needs Rust 1.86
+fn next_up(x: f64) -> f64 {
+    if x.is_nan() || x == f64::INFINITY {
+        return x;
+    }
+    if x == 0.0 {
+        // Either zero steps up to the smallest subnormal
+        return f64::from_bits(1);
+    }
+    // Away from zero the bits are ordered the same way as the magnitudes
+    let bits = x.to_bits();
+    f64::from_bits(if x > 0.0 { bits + 1 } else { bits - 1 })
+}
+
+/// The next f64 below `x`, the same as `f64::next_down`
+fn next_down(x: f64) -> f64 {
+    -next_up(-x)
+}
+
+/// A closed interval [lo, hi]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Self {
+        Self { lo, hi }
+    }
+
+    pub fn point(value: f64) -> Self {
+        Self { lo: value, hi: value }
+    }
+
+    /// Builds an interval from bounds computed with round to nearest, so each is at most half an ulp
+    /// from the true bound
+    fn outward(lo: f64, hi: f64) -> Self {
+        Self { lo: next_down(lo), hi: next_up(hi) }
+    }
+
+    /// Encloses the true value of an elementary function whose platform result was `value`
+    fn elementary(value: f64) -> Self {
+        let error = value.abs() * (ELEMENTARY_ULPS * f64::EPSILON) + f64::MIN_POSITIVE;
+        Self::outward(value - error, value + error)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.0
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    /// An upper bound on the distance from the midpoint to either end
+    fn radius(&self) -> f64 {
+        let midpoint = self.midpoint();
+        next_up((midpoint - self.lo).max(self.hi - midpoint))
+    }
+
+    fn intersection(&self, other: Self) -> Self {
+        Self { lo: self.lo.max(other.lo), hi: self.hi.min(other.hi) }
+    }
+
+    /// Returns `None` if the divisor contains zero
+    fn checked_div(self, other: Self) -> Option<Self> {
+        if other.lo <= 0.0 && other.hi >= 0.0 {
+            return None;
+        }
+        let quotients = [self.lo / other.lo, self.lo / other.hi, self.hi / other.lo, self.hi / other.hi];
+        Some(Self::outward(quotients.into_iter().fold(f64::INFINITY, f64::min), quotients.into_iter().fold(f64::NEG_INFINITY, f64::max)))
+    }
+
+    /// sin and cos are 1-Lipschitz, so they move at most the radius away from their midpoint values
+    pub(crate) fn sin_cos(self) -> (Self, Self) {
+        let midpoint = self.midpoint();
+        let radius = Self::new(-self.radius(), self.radius());
+        let unit = Self::new(-1.0, 1.0);
+        let sin = (Self::elementary(midpoint.sin()) + radius).intersection(unit);
+        let cos = (Self::elementary(midpoint.cos()) + radius).intersection(unit);
+        (sin, cos)
+    }
+
+    /// sinh is increasing, and cosh is increasing in |x|
+    pub(crate) fn sinh_cosh(self) -> (Self, Self) {
+        let sinh = Self::new(Self::elementary(self.lo.sinh()).lo, Self::elementary(self.hi.sinh()).hi);
+        let smallest = if self.contains(0.0) { 0.0 } else { self.lo.abs().min(self.hi.abs()) };
+        let largest = self.lo.abs().max(self.hi.abs());
+        let cosh = Self::new(Self::elementary(smallest.cosh()).lo.max(1.0), Self::elementary(largest.cosh()).hi);
+        (sinh, cosh)
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::outward(self.lo + other.lo, self.hi + other.hi)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::outward(self.lo - other.hi, self.hi - other.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let products = [self.lo * other.lo, self.lo * other.hi, self.hi * other.lo, self.hi * other.hi];
+        Self::outward(products.into_iter().fold(f64::INFINITY, f64::min), products.into_iter().fold(f64::NEG_INFINITY, f64::max))
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { lo: -self.hi, hi: -self.lo }
+    }
+}
+
+/// The interval Newton operator, or `None` if the derivative might be zero on `x`
+fn newton(x: Interval, f: &impl Fn(Interval) -> Interval, f_prime: &impl Fn(Interval) -> Interval) -> Option<Interval> {
+    let midpoint = Interval::point(x.midpoint());
+    Some(midpoint - f(midpoint).checked_div(f_prime(x))?)
+}
+
+/// Finds an interval around `estimate` that provably contains exactly one root of `f`, then
+/// shrinks it with further Newton steps. `f` and `f_prime` must enclose the function and its
+/// derivative over any interval they're given
+pub(crate) fn certify(estimate: f64, f: impl Fn(Interval) -> Interval, f_prime: impl Fn(Interval) -> Interval) -> Result<Interval, SolveError> {
+    let mut radius = estimate.abs().max(1.0) * INITIAL_RADIUS;
+    for _ in 0..CERTIFY_ATTEMPTS {
+        let x = Interval::new(estimate - radius, estimate + radius);
+        let certified = newton(x, &f, &f_prime)
+            .filter(|n| n.lo > x.lo && n.hi < x.hi && n.lo.is_finite() && n.hi.is_finite());
+        if let Some(mut enclosure) = certified {
+            // Every further Newton step still contains the root, so keep whatever it rules out
+            for _ in 0..MAX_CONTRACTIONS {
+                let Some(next) = newton(enclosure, &f, &f_prime) else { break };
+                let next = next.intersection(enclosure);
+                if next.width() >= enclosure.width() {
+                    break;
+                }
+                enclosure = next;
+            }
+            return Ok(enclosure);
+        }
+        radius *= RADIUS_GROWTH;
+    }
+    Err(SolveError::NotCertified)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{certify, next_down, next_up, Interval};
+
+    #[test]
+    fn test_next_up_down() {
+        let smallest = f64::from_bits(1);
+        let cases = [
+            (1.0, 1.0 + f64::EPSILON, 1.0 - f64::EPSILON / 2.0),
+            (-1.0, -1.0 + f64::EPSILON / 2.0, -1.0 - f64::EPSILON),
+            (0.0, smallest, -smallest),
+            (-0.0, smallest, -smallest),
+            (smallest, 2.0 * smallest, 0.0),
+            (f64::MAX, f64::INFINITY, f64::from_bits(f64::MAX.to_bits() - 1)),
+            (f64::INFINITY, f64::INFINITY, f64::MAX),
+            (f64::NEG_INFINITY, -f64::MAX, f64::NEG_INFINITY),
+        ];
+        for (x, up, down) in cases {
+            assert_eq!(next_up(x), up, "{}", x);
+            assert_eq!(next_down(x), down, "{}", x);
+        }
+        // The step down from the smallest positive subnormal lands on +0, and up from its
+        // negative on -0
+        assert!(next_down(smallest).is_sign_positive() && next_up(-smallest).is_sign_negative());
+        assert!(next_up(f64::NAN).is_nan() && next_down(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_interval_operations() {
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(-3.0, 0.5);
+        assert!((a + b).contains(-2.0) && (a + b).contains(2.5));
+        assert!((a - b).contains(0.5) && (a - b).contains(5.0));
+        assert!((a * b).contains(-6.0) && (a * b).contains(1.0));
+        assert!(a.checked_div(b).is_none());
+        assert!(b.checked_div(a).unwrap().contains(-3.0) && b.checked_div(a).unwrap().contains(0.5));
+
+        let third = Interval::point(1.0).checked_div(Interval::point(3.0)).unwrap();
+        assert!(third.lo < third.hi && third.width() < 1.0e-15);
+
+        let (sin, cos) = Interval::new(0.5, 0.6).sin_cos();
+        assert!(sin.lo <= 0.5_f64.sin() && sin.hi >= 0.6_f64.sin());
+        assert!(cos.lo <= 0.6_f64.cos() && cos.hi >= 0.5_f64.cos());
+        let (sinh, cosh) = Interval::new(-0.5, 0.25).sinh_cosh();
+        assert!(sinh.lo <= (-0.5_f64).sinh() && sinh.hi >= 0.25_f64.sinh());
+        assert!(cosh.lo <= 1.0 && cosh.hi >= 0.5_f64.cosh());
+    }
+
+    #[test]
+    fn test_certify() {
+        let square = |x: Interval| x * x - Interval::point(2.0);
+        let derivative = |x: Interval| Interval::point(2.0) * x;
+        let root = certify(1.414, square, derivative).unwrap();
+        assert!(root.contains(2.0_f64.sqrt()));
+        assert!(root.width() < 1.0e-15);
+        // A double root can't be certified since the derivative vanishes there
+        assert!(certify(1.0e-12, |x: Interval| x * x, derivative).is_err());
+    }
+}
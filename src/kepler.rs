@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{Precision, SolverConfig}, ellipse::EllipseSolver, error::SolveError, float::Float, hyperbola::HyperbolaSolver, parabola::ParabolaSolver};
+
+/// Eccentricities within this distance of 1 are treated as parabolic by `KeplerSolver::new`
+pub const DEFAULT_PARABOLIC_TOLERANCE: f64 = 1.0e-6;
+
+/// An anomaly tagged with the kind of orbit it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Anomaly<T = f64> {
+    /// The eccentric anomaly E of an elliptic orbit
+    Eccentric(T),
+    /// The parabolic anomaly D = tan(true anomaly / 2) of a parabolic orbit
+    Parabolic(T),
+    /// The hyperbolic anomaly F of a hyperbolic orbit
+    Hyperbolic(T),
+}
+
+impl<T: Float> Anomaly<T> {
+    pub fn value(&self) -> T {
+        match self {
+            Anomaly::Eccentric(value) | Anomaly::Parabolic(value) | Anomaly::Hyperbolic(value) => *value,
+        }
+    }
+}
+
+/// Picks the elliptic, parabolic or hyperbolic solver based on eccentricity. The mean anomaly
+/// passed to `solve` is interpreted in the convention of the chosen solver, so n * (t - T) with
+/// n = sqrt(mu / |a|^3) for ellipses and hyperbolas, and sqrt(mu / (2 q^3)) * (t - T) for parabolas.
+/// Match on the variant if you need to know which convention applies before solving.
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::kepler::{Anomaly, KeplerSolver};
+///
+/// fn example_kepler() {
+///     let solver = KeplerSolver::new(1.5);
+///     match solver.solve(1.2) {
+///         Anomaly::Eccentric(e) => println!("E = {}", e),
+///         Anomaly::Parabolic(d) => println!("D = {}", d),
+///         Anomaly::Hyperbolic(f) => println!("F = {}", f),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KeplerSolver<T = f64> {
+    Ellipse(EllipseSolver<T>),
+    Parabola(ParabolaSolver),
+    Hyperbola(HyperbolaSolver<T>),
+}
+
+impl<T: Float> KeplerSolver<T> {
+    pub fn new(eccentricity: T) -> Self {
+        Self::with_parabolic_tolerance(eccentricity, T::from_f64(DEFAULT_PARABOLIC_TOLERANCE))
+    }
+
+    /// Eccentricities within `parabolic_tolerance` of 1 are treated as parabolic
+    pub fn with_parabolic_tolerance(eccentricity: T, parabolic_tolerance: T) -> Self {
+        Self::build(eccentricity, parabolic_tolerance, Precision::Balanced)
+    }
+
+    /// Same as `new`, but with the elliptic or hyperbolic solver using the given preset. The
+    /// parabolic solver is exact, so has no presets
+    pub fn with_precision(eccentricity: T, precision: Precision) -> Self {
+        Self::build(eccentricity, T::from_f64(DEFAULT_PARABOLIC_TOLERANCE), precision)
+    }
+
+    fn build(eccentricity: T, parabolic_tolerance: T, precision: Precision) -> Self {
+        if (eccentricity - T::from_f64(1.0)).abs() <= parabolic_tolerance {
+            KeplerSolver::Parabola(ParabolaSolver::new())
+        } else if eccentricity < T::from_f64(1.0) {
+            KeplerSolver::Ellipse(EllipseSolver::with_config(eccentricity, SolverConfig::ellipse(precision)))
+        } else {
+            KeplerSolver::Hyperbola(HyperbolaSolver::with_config(eccentricity, SolverConfig::hyperbola(precision)))
+        }
+    }
+
+    pub fn solve(&self, mean_anomaly: T) -> Anomaly<T> {
+        match self {
+            KeplerSolver::Ellipse(solver) => Anomaly::Eccentric(solver.solve(mean_anomaly)),
+            KeplerSolver::Parabola(solver) => Anomaly::Parabolic(solver.solve(mean_anomaly)),
+            KeplerSolver::Hyperbola(solver) => Anomaly::Hyperbolic(solver.solve(mean_anomaly)),
+        }
+    }
+
+    pub fn try_solve(&self, mean_anomaly: T) -> Result<Anomaly<T>, SolveError> {
+        match self {
+            KeplerSolver::Ellipse(solver) => solver.try_solve(mean_anomaly).map(Anomaly::Eccentric),
+            KeplerSolver::Parabola(solver) => solver.try_solve(mean_anomaly).map(Anomaly::Parabolic),
+            KeplerSolver::Hyperbola(solver) => solver.try_solve(mean_anomaly).map(Anomaly::Hyperbolic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ellipse::EllipseSolver, error::SolveError, hyperbola::HyperbolaSolver, parabola::ParabolaSolver};
+
+    use super::{Anomaly, KeplerSolver};
+
+    #[test]
+    fn test_kepler_dispatch() {
+        assert!(matches!(KeplerSolver::new(0.0), KeplerSolver::Ellipse(_)));
+        assert!(matches!(KeplerSolver::new(0.999), KeplerSolver::Ellipse(_)));
+        assert!(matches!(KeplerSolver::new(1.0), KeplerSolver::Parabola(_)));
+        assert!(matches!(KeplerSolver::new(1.0 - 1.0e-7), KeplerSolver::Parabola(_)));
+        assert!(matches!(KeplerSolver::new(1.0 + 1.0e-7), KeplerSolver::Parabola(_)));
+        assert!(matches!(KeplerSolver::new(1.001), KeplerSolver::Hyperbola(_)));
+        assert!(matches!(KeplerSolver::with_parabolic_tolerance(0.99, 0.1), KeplerSolver::Parabola(_)));
+        assert!(matches!(KeplerSolver::with_parabolic_tolerance(1.0 + 1.0e-7, 0.0), KeplerSolver::Hyperbola(_)));
+    }
+
+    #[test]
+    fn test_kepler_solve() {
+        for m in [0.0, 0.5, 1.0, 3.0, 6.0] {
+            assert_eq!(KeplerSolver::new(0.5).solve(m), Anomaly::Eccentric(EllipseSolver::new(0.5).solve(m)));
+            assert_eq!(KeplerSolver::new(1.0).solve(m), Anomaly::Parabolic(ParabolaSolver::new().solve(m)));
+            assert_eq!(KeplerSolver::new(1.5).solve(m), Anomaly::Hyperbolic(HyperbolaSolver::new(1.5).solve(m)));
+        }
+    }
+
+    #[test]
+    fn test_kepler_try_solve() {
+        assert_eq!(KeplerSolver::new(0.5).try_solve(1.0), Ok(KeplerSolver::new(0.5).solve(1.0)));
+        assert_eq!(KeplerSolver::new(1.0).try_solve(f64::NAN), Err(SolveError::NonFiniteInput));
+        assert_eq!(KeplerSolver::new(f64::NAN).try_solve(1.0), Err(SolveError::NonFiniteInput));
+        assert_eq!(KeplerSolver::new(-0.5).try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: -0.5 }));
+    }
+}
@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::SolveError, float::Float, vector::{add, cross, norm, scale, sub}};
+
+// Limits on the iterations, from Izzo's reference implementation
+const MAX_HOUSEHOLDER_ITERATIONS: usize = 15;
+const MAX_MINIMUM_TIME_ITERATIONS: usize = 12;
+
+// Outside this distance from x = 1, the time of flight uses Lancaster's expression, inside it
+// Lagrange's, and very close to 1 Battin's series, since the other two lose precision there
+const LAGRANGE_DISTANCE: f64 = 0.2;
+const BATTIN_DISTANCE: f64 = 0.01;
+
+/// The direction of the transfer around the z axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// Anticlockwise when seen from +z
+    Prograde,
+    /// Clockwise when seen from +z
+    Retrograde,
+}
+
+/// Which of the two solutions with the same number of revolutions. Following Izzo, the left
+/// solution has the smaller value of the iteration variable x, which is the one with the longer
+/// path before the minimum-energy transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Branch {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LambertSolution<T = f64> {
+    pub departure_velocity: [T; 3],
+    pub arrival_velocity: [T; 3],
+    pub revolutions: usize,
+    /// `None` for the zero revolution solution, which is unique
+    pub branch: Option<Branch>,
+}
+
+/// Finds every orbit that goes from `departure_position` to `arrival_position` in `time_of_flight`
+/// in the given direction, with up to `max_revolutions` complete revolutions on the way, using the
+/// method of Dario Izzo (https://doi.org/10.1007/s10569-014-9587-y). Returns the zero revolution
+/// solution first, followed by the left and right solutions for each number of revolutions that has
+/// solutions, so there are 2n + 1 solutions where n may be less than `max_revolutions` if the time
+/// of flight is too short for more.
+///
+/// Returns an error if the positions are collinear, so the plane of the transfer is undefined, or
+/// if the plane contains the z axis, so `direction` is ambiguous.
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::lambert::{self, Direction};
+///
+/// fn example_lambert() {
+///     let solutions = lambert::solve([7.0e6, 0.0, 0.0], [0.0, 8.0e6, 1.0e6], 3000.0, 3.986e14, Direction::Prograde, 0).unwrap();
+///     println!("{:?}", solutions[0].departure_velocity);
+/// }
+/// ```
+pub fn solve<T: Float>(
+    departure_position: [T; 3],
+    arrival_position: [T; 3],
+    time_of_flight: T,
+    gravitational_parameter: T,
+    direction: Direction,
+    max_revolutions: usize,
+) -> Result<Vec<LambertSolution<T>>, SolveError> {
+    let c = T::from_f64;
+    if !departure_position.iter().chain(&arrival_position).all(|x| x.is_finite()) || !time_of_flight.is_finite() || !gravitational_parameter.is_finite() {
+        return Err(SolveError::NonFiniteInput);
+    }
+    if time_of_flight <= c(0.0) {
+        return Err(SolveError::InvalidTimeOfFlight { time_of_flight: time_of_flight.to_f64() });
+    }
+
+    // Geometry of the problem, in the notation of the paper
+    let chord = norm(sub(arrival_position, departure_position));
+    let r1 = norm(departure_position);
+    let r2 = norm(arrival_position);
+    let semi_perimeter = (chord + r1 + r2) / c(2.0);
+    let ir1 = scale(departure_position, c(1.0) / r1);
+    let ir2 = scale(arrival_position, c(1.0) / r2);
+    let ih = cross(ir1, ir2);
+    let ih_norm = norm(ih);
+    if ih_norm == c(0.0) || ih[2] == c(0.0) {
+        return Err(SolveError::DegenerateTransfer);
+    }
+    let ih = scale(ih, c(1.0) / ih_norm);
+
+    let mut lambda = T::sqrt((c(1.0) - chord / semi_perimeter).max(c(0.0)));
+    let (mut it1, mut it2) = if ih[2] < c(0.0) {
+        // The transfer angle is more than 180 degrees when seen from +z
+        lambda = -lambda;
+        (cross(ir1, ih), cross(ir2, ih))
+    } else {
+        (cross(ih, ir1), cross(ih, ir2))
+    };
+    it1 = scale(it1, c(1.0) / norm(it1));
+    it2 = scale(it2, c(1.0) / norm(it2));
+    if direction == Direction::Retrograde {
+        lambda = -lambda;
+        it1 = scale(it1, c(-1.0));
+        it2 = scale(it2, c(-1.0));
+    }
+
+    // Non-dimensional time of flight
+    let t = T::sqrt(c(2.0) * gravitational_parameter / semi_perimeter.powi(3)) * time_of_flight;
+    let revolutions = find_max_revolutions(lambda, t).min(max_revolutions);
+
+    let mut xs = vec![(solve_single_revolution(lambda, t)?, 0, None)];
+    for n in 1..=revolutions {
+        let nf = c(n as f64);
+        let left = (((nf + c(1.0)) * T::PI) / (c(8.0) * t)).powf(c(2.0 / 3.0));
+        let right = ((c(8.0) * t) / (nf * T::PI)).powf(c(2.0 / 3.0));
+        let tolerance = c(1.0e-8).max(T::EPSILON * c(16.0));
+        xs.push((householder(lambda, t, (left - c(1.0)) / (left + c(1.0)), n, tolerance)?, n, Some(Branch::Left)));
+        xs.push((householder(lambda, t, (right - c(1.0)) / (right + c(1.0)), n, tolerance)?, n, Some(Branch::Right)));
+    }
+
+    // Reconstruct the velocities from x
+    let gamma = T::sqrt(gravitational_parameter * semi_perimeter / c(2.0));
+    let rho = (r1 - r2) / chord;
+    let sigma = T::sqrt((c(1.0) - rho * rho).max(c(0.0)));
+    Ok(xs.into_iter().map(|(x, revolutions, branch)| {
+        let y = T::sqrt(c(1.0) - lambda * lambda + lambda * lambda * x * x);
+        let vr1 = gamma * ((lambda * y - x) - rho * (lambda * y + x)) / r1;
+        let vr2 = -gamma * ((lambda * y - x) + rho * (lambda * y + x)) / r2;
+        let vt = gamma * sigma * (y + lambda * x);
+        LambertSolution {
+            departure_velocity: add(scale(ir1, vr1), scale(it1, vt / r1)),
+            arrival_velocity: add(scale(ir2, vr2), scale(it2, vt / r2)),
+            revolutions,
+            branch,
+        }
+    }).collect())
+}
+
+/// The largest number of revolutions for which there's a solution with non-dimensional time of
+/// flight `t`
+fn find_max_revolutions<T: Float>(lambda: T, t: T) -> usize {
+    let c = T::from_f64;
+    let mut max_revolutions = (t / T::PI).floor().to_f64() as usize;
+    let t00 = lambda.acos() + lambda * T::sqrt(c(1.0) - lambda * lambda);
+    let t0 = t00 + c(max_revolutions as f64) * T::PI;
+    if max_revolutions > 0 && t < t0 {
+        // Halley iterations for the minimum time of flight with max_revolutions revolutions. If it's
+        // above t, that number of revolutions has no solutions
+        let mut t_min = t0;
+        let mut x_old = c(0.0);
+        let mut x_new = c(0.0);
+        for _ in 0..=MAX_MINIMUM_TIME_ITERATIONS {
+            let (dt, ddt, dddt) = time_of_flight_derivatives(lambda, x_old, t_min);
+            if dt != c(0.0) {
+                x_new = x_old - dt * ddt / (ddt * ddt - dt * dddt / c(2.0));
+            }
+            if (x_old - x_new).abs() < c(1.0e-13) {
+                break;
+            }
+            t_min = time_of_flight(lambda, x_new, max_revolutions);
+            x_old = x_new;
+        }
+        if t_min > t {
+            max_revolutions -= 1;
+        }
+    }
+    max_revolutions
+}
+
+fn solve_single_revolution<T: Float>(lambda: T, t: T) -> Result<T, SolveError> {
+    let c = T::from_f64;
+    let lambda2 = lambda * lambda;
+    let lambda3 = lambda2 * lambda;
+    let t00 = lambda.acos() + lambda * T::sqrt(c(1.0) - lambda2);
+    let t1 = c(2.0 / 3.0) * (c(1.0) - lambda3);
+    let x0 = if t >= t00 {
+        -(t - t00) / (t - t00 + c(4.0))
+    } else if t <= t1 {
+        t1 * (t1 - t) / (c(2.0 / 5.0) * (c(1.0) - lambda2 * lambda3) * t) + c(1.0)
+    } else {
+        (t / t00).powf(c(std::f64::consts::LN_2) / (t1 / t00).ln()) - c(1.0)
+    };
+    householder(lambda, t, x0, 0, c(1.0e-5))
+}
+
+/// Householder iterations on x until the time of flight is `t`
+fn householder<T: Float>(lambda: T, t: T, mut x: T, revolutions: usize, tolerance: T) -> Result<T, SolveError> {
+    let c = T::from_f64;
+    for _ in 0..MAX_HOUSEHOLDER_ITERATIONS {
+        let tof = time_of_flight(lambda, x, revolutions);
+        let (dt, ddt, dddt) = time_of_flight_derivatives(lambda, x, tof);
+        let delta = tof - t;
+        let dt2 = dt * dt;
+        let x_new = x - delta * (dt2 - delta * ddt / c(2.0)) / (dt * (dt2 - delta * ddt) + dddt * delta * delta / c(6.0));
+        let error = (x - x_new).abs();
+        x = x_new;
+        if error <= tolerance {
+            return Ok(x);
+        }
+    }
+    Err(SolveError::NotConverged { iterations: MAX_HOUSEHOLDER_ITERATIONS })
+}
+
+/// First three derivatives of the non-dimensional time of flight `t` with respect to x
+fn time_of_flight_derivatives<T: Float>(lambda: T, x: T, t: T) -> (T, T, T) {
+    let c = T::from_f64;
+    let l2 = lambda * lambda;
+    let l3 = l2 * lambda;
+    let umx2 = c(1.0) - x * x;
+    let y = T::sqrt(c(1.0) - l2 * umx2);
+    let y2 = y * y;
+    let y3 = y2 * y;
+    let dt = c(1.0) / umx2 * (c(3.0) * t * x - c(2.0) + c(2.0) * l3 * x / y);
+    let ddt = c(1.0) / umx2 * (c(3.0) * t + c(5.0) * x * dt + c(2.0) * (c(1.0) - l2) * l3 / y3);
+    let dddt = c(1.0) / umx2 * (c(7.0) * x * ddt + c(8.0) * dt - c(6.0) * (c(1.0) - l2) * l2 * l3 * x / y3 / y2);
+    (dt, ddt, dddt)
+}
+
+/// Non-dimensional time of flight as a function of x
+fn time_of_flight<T: Float>(lambda: T, x: T, revolutions: usize) -> T {
+    let c = T::from_f64;
+    let n = c(revolutions as f64);
+    let distance = (x - c(1.0)).abs();
+    if distance < c(LAGRANGE_DISTANCE) && distance > c(BATTIN_DISTANCE) {
+        return time_of_flight_lagrange(lambda, x, n);
+    }
+    let k = lambda * lambda;
+    let e = x * x - c(1.0);
+    let rho = e.abs();
+    let z = T::sqrt(c(1.0) + k * e);
+    if distance < c(BATTIN_DISTANCE) {
+        let eta = z - lambda * x;
+        let s1 = c(0.5) * (c(1.0) - lambda - x * eta);
+        let q = c(4.0 / 3.0) * hypergeometric(s1, c(1.0e-11));
+        (eta.powi(3) * q + c(4.0) * lambda * eta) / c(2.0) + n * T::PI / rho.powf(c(1.5))
+    } else {
+        let y = rho.sqrt();
+        let g = x * z - lambda * e;
+        let d = if e < c(0.0) {
+            n * T::PI + g.acos()
+        } else {
+            let f = y * (z - lambda * x);
+            (f + g).ln()
+        };
+        (x - lambda * z - d / y) / e
+    }
+}
+
+fn time_of_flight_lagrange<T: Float>(lambda: T, x: T, n: T) -> T {
+    let c = T::from_f64;
+    let a = c(1.0) / (c(1.0) - x * x);
+    if a > c(0.0) {
+        let alpha = c(2.0) * x.acos();
+        let mut beta = c(2.0) * T::asin(T::sqrt(lambda * lambda / a));
+        if lambda < c(0.0) {
+            beta = -beta;
+        }
+        a * a.sqrt() * ((alpha - alpha.sin()) - (beta - beta.sin()) + c(2.0) * T::PI * n) / c(2.0)
+    } else {
+        let alpha = c(2.0) * x.acosh();
+        let mut beta = c(2.0) * T::asinh(T::sqrt(-lambda * lambda / a));
+        if lambda < c(0.0) {
+            beta = -beta;
+        }
+        -a * (-a).sqrt() * ((beta - beta.sinh()) - (alpha - alpha.sinh())) / c(2.0)
+    }
+}
+
+/// The hypergeometric function 2F1(3, 1, 5/2, z) used by Battin's series
+fn hypergeometric<T: Float>(z: T, tolerance: T) -> T {
+    let c = T::from_f64;
+    let mut sum = c(1.0);
+    let mut term = c(1.0);
+    let mut j = c(0.0);
+    // The series converges for |z| < 1, but cap the terms anyway in case z is garbage
+    for _ in 0..1000 {
+        term = term * (c(3.0) + j) * (c(1.0) + j) / (c(2.5) + j) * z / (j + c(1.0));
+        sum += term;
+        j += c(1.0);
+        if term.abs() <= tolerance {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{error::SolveError, orbit::Orbit};
+
+    use super::{solve, Branch, Direction};
+
+    const MU: f64 = 3.986_004_418e14;
+
+    fn norm(v: [f64; 3]) -> f64 {
+        (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+    }
+
+    fn assert_close(expected: [f64; 3], actual: [f64; 3], tolerance: f64) {
+        for i in 0..3 {
+            if (expected[i] - actual[i]).abs() > tolerance * norm(expected) {
+                dbg!(expected, actual);
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_lambert_propagates_to_arrival() {
+        let departures = [[7.0e6, 0.0, 0.0], [-3.0e6, 6.0e6, 1.0e6], [1.0e7, 2.0e6, -4.0e6]];
+        let arrivals = [[0.0, 8.0e6, 1.0e6], [4.0e6, -9.0e6, 3.0e6], [-2.0e7, 1.0e6, 5.0e5]];
+        for r1 in departures {
+            for r2 in arrivals {
+                for direction in [Direction::Prograde, Direction::Retrograde] {
+                    // From fast hyperbolic transfers to slow multi revolution ones
+                    for time_of_flight in [300.0, 3000.0, 20000.0, 100000.0] {
+                        let solutions = solve(r1, r2, time_of_flight, MU, direction, 5).unwrap();
+                        assert_eq!(solutions.len() % 2, 1);
+                        for solution in &solutions {
+                            let orbit = Orbit::from_state(r1, solution.departure_velocity, MU, 0.0).unwrap();
+                            let (position, velocity) = orbit.state_at(time_of_flight);
+                            assert_close(r2, position, 1.0e-7);
+                            assert_close(solution.arrival_velocity, velocity, 1.0e-7);
+                            // Check the direction from the angular momentum
+                            let h_z = r1[0] * solution.departure_velocity[1] - r1[1] * solution.departure_velocity[0];
+                            assert_eq!(h_z > 0.0, direction == Direction::Prograde);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lambert_multiple_revolutions() {
+        let solutions = solve([7.0e6, 0.0, 0.0], [0.0, 7.0e6, 0.0], 100000.0, MU, Direction::Prograde, 100).unwrap();
+        // The minimum energy transfer has a = s / 2 and a period of about 4590s, so there's room for 21
+        // revolutions
+        let revolutions = solutions.last().unwrap().revolutions;
+        assert_eq!(revolutions, 21);
+        assert_eq!(solutions.len(), 2 * revolutions + 1);
+        assert_eq!((solutions[0].revolutions, solutions[0].branch), (0, None));
+        for n in 1..=revolutions {
+            assert_eq!((solutions[2 * n - 1].revolutions, solutions[2 * n - 1].branch), (n, Some(Branch::Left)));
+            assert_eq!((solutions[2 * n].revolutions, solutions[2 * n].branch), (n, Some(Branch::Right)));
+        }
+        for solution in &solutions {
+            let orbit = Orbit::from_state([7.0e6, 0.0, 0.0], solution.departure_velocity, MU, 0.0).unwrap();
+            assert_close([0.0, 7.0e6, 0.0], orbit.state_at(100000.0).0, 1.0e-7);
+        }
+        assert_eq!(solve([7.0e6, 0.0, 0.0], [0.0, 7.0e6, 0.0], 100000.0, MU, Direction::Prograde, 2).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_lambert_errors() {
+        assert_eq!(solve([7.0e6, 0.0, 0.0], [-8.0e6, 0.0, 0.0], 3000.0, MU, Direction::Prograde, 0), Err(SolveError::DegenerateTransfer));
+        assert_eq!(solve([7.0e6, 0.0, 0.0], [0.0, 0.0, 8.0e6], 3000.0, MU, Direction::Prograde, 0), Err(SolveError::DegenerateTransfer));
+        assert_eq!(solve([7.0e6, 0.0, 0.0], [0.0, 8.0e6, 0.0], -3000.0, MU, Direction::Prograde, 0), Err(SolveError::InvalidTimeOfFlight { time_of_flight: -3000.0 }));
+        assert_eq!(solve([f64::NAN, 0.0, 0.0], [0.0, 8.0e6, 0.0], 3000.0, MU, Direction::Prograde, 0), Err(SolveError::NonFiniteInput));
+    }
+}
@@ -1,4 +1,24 @@
+pub mod anomaly;
 #[cfg(test)]
 mod bisection;
+pub mod config;
+pub mod double_double;
+pub mod dual;
 pub mod ellipse;
-pub mod hyperbola;
\ No newline at end of file
+pub mod elements;
+pub mod error;
+pub mod float;
+pub mod hyperbola;
+pub mod interval;
+pub mod kepler;
+pub mod lambert;
+pub mod method;
+pub mod orbit;
+pub mod parabola;
+pub mod partials;
+pub mod reference;
+pub mod report;
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd;
+pub mod universal;
+mod vector;
\ No newline at end of file
@@ -0,0 +1,692 @@
+//! The algorithms `EllipseSolver` can use. Each is a starter, which gives an initial estimate of the
+//! eccentric anomaly, and a correction that's iterated until it falls below the configured
+//! tolerance, unless the starter is accurate enough to need none. The solver handles everything
+//! else (range reduction, symmetry, the iteration limit and refinement steps), so every method
+//! works with the whole of the `EllipseSolver` API.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{Precision, SolverConfig}, ellipse::{kepler_residual, laguerre_step, rotate, seed, EllipseSolver}, float::Float};
+
+/// The coefficient of Danby's starter, which is about the best single constant over all
+/// eccentricities
+const DANBY_COEFFICIENT: f64 = 0.85;
+
+/// Markley's empirical coefficient in the Pade approximation of sin(E), fitted to minimise the error
+/// of the starter
+const MARKLEY_COEFFICIENT: f64 = 1.6;
+
+/// Nodes on the upper half of the contour; the lower half follows by symmetry
+const CONTOUR_NODES: usize = 12;
+/// The radius of the contour as a fraction of the estimated distance from the root to the nearest
+/// complex root. The quadrature error is about this to the power of twice `CONTOUR_NODES`
+const CONTOUR_RADIUS_RATIO: f64 = 0.1;
+/// Terms of the Taylor series of 1 - cos(h) and h - sin(h) at the nodes
+const CONTOUR_SERIES_TERMS: usize = 8;
+
+/// Segments of the table that are equally spaced in the eccentric anomaly over [0, pi]
+const PIECEWISE_SEGMENTS: usize = 16;
+/// The first segment is split into halves, quarters and so on until the smallest is at most this
+/// times sqrt(1 - e), below which E(M) is close to linear
+const PIECEWISE_GRADING: f64 = 1.0;
+/// Degree of the polynomial on each segment
+const PIECEWISE_DEGREE: usize = 8;
+
+/// ENRKE's special treatment applies for eccentricities above this and mean anomalies below
+/// `ENRKE_CORNER_MEAN_ANOMALY`, where E(M) is close to the cube root of 6M
+const ENRKE_CORNER_ECCENTRICITY: f64 = 0.99;
+const ENRKE_CORNER_MEAN_ANOMALY: f64 = 0.0045;
+/// In the corner, the root is bracketed by 2.7M below and 0.301 above
+const ENRKE_CORNER_LOWER: f64 = 2.7;
+const ENRKE_CORNER_UPPER: f64 = 0.301;
+/// Bisections of the corner bracket, which leave it about 1e-3 wide
+const ENRKE_BISECTIONS: usize = 8;
+/// Smaller tolerances are raised to this many times pi times the machine epsilon, a couple of ulps
+/// of pi, since the stopping test can't tell errors below that apart from rounding
+const ENRKE_MIN_TOLERANCE: f64 = 4.0;
+
+/// An algorithm for solving E - e sin(E) = M for 0 <= M <= pi
+pub trait KeplerMethod<T: Float> {
+    /// Whether the seed is already the solution. The solver then returns it with no iteration at
+    /// all, so the tolerances and iteration limit don't apply, though refinement steps still do
+    const NON_ITERATIVE: bool = false;
+
+    /// Whether the correction that passes the convergence test is applied. By default it isn't, so
+    /// that the solver can keep the sine and cosine it was computed with. Methods whose test bounds
+    /// the error after the correction, like `Enrke`, apply it, and the solver carries the sine and
+    /// cosine through it with the addition formulas
+    const APPLIES_FINAL_DELTA: bool = false;
+
+    /// Initial estimate of the eccentric anomaly
+    fn seed(&self, ec: T, mean_anomaly: T) -> T;
+
+    /// `seed` with its sine and cosine, for methods that can get them more cheaply than by
+    /// evaluating them again
+    fn seed_with_trig(&self, ec: T, mean_anomaly: T) -> (T, T, T) {
+        let eccentric_anomaly = self.seed(ec, mean_anomaly);
+        (eccentric_anomaly, eccentric_anomaly.sin(), eccentric_anomaly.cos())
+    }
+
+    /// The correction to add to `eccentric_anomaly`. `sin` and `cos` are those of
+    /// `eccentric_anomaly`, and are passed in so the solver can keep them
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T;
+
+    /// Whether the iteration has converged, given the next correction to `eccentric_anomaly`. By
+    /// default this is the solver's configured tolerance. The eccentricity and the cosine of
+    /// `eccentric_anomaly` are passed for tests that need the derivative of Kepler's equation
+    fn converged(&self, config: &SolverConfig<T>, delta: T, eccentric_anomaly: T, _ec: T, _cos: T) -> bool {
+        config.converged(delta, eccentric_anomaly)
+    }
+}
+
+/// Tommasini and Olivieri's seed followed by Laguerre's method, which is what `EllipseSolver::new`
+/// uses. Laguerre's method converges cubically and very reliably for any starting point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Laguerre;
+
+/// Tommasini and Olivieri's seed followed by Newton's method, which converges quadratically and
+/// has the cheapest step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Newton;
+
+/// Tommasini and Olivieri's seed followed by Halley's method, which converges cubically
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halley;
+
+/// Danby's starter, E = M + 0.85e, followed by Danby and Burkardt's quartically convergent
+/// correction (https://doi.org/10.1007/BF01227542)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Danby;
+
+/// Mikkola's cubic starter (https://doi.org/10.1007/BF01235540), which solves a cubic
+/// approximation of Kepler's equation in sin(E/3) and is good to a few thousandths of a radian
+/// everywhere, followed by the same quartic correction as `Danby`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mikkola;
+
+/// Markley's non-iterative solver (https://doi.org/10.1007/BF00691917): a cubic Pade approximation
+/// of Kepler's equation, solved in closed form, followed by a single fifth-order correction. This
+/// is accurate to about 1e-15 radians, so the solver returns it directly, and every solve costs one
+/// cube root, one square root and one sine and cosine. The sine and cosine of the result come from
+/// those of the starter by the addition formulas, since the correction is tiny
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Markley;
+
+/// Philcox, Goodman and Slepian's contour integral method (https://doi.org/10.1093/mnras/stab1032).
+/// The root is the ratio of the contour integrals of z / f(z) and 1 / f(z) around a circle
+/// containing it and no other root, and the trapezoidal rule on a circle converges geometrically,
+/// at a rate set by how much closer the root is to the centre than the other roots are. The circle
+/// is centred on Markley's starter, which is close enough that 24 nodes get the root to rounding
+/// as long as the radius is a fixed fraction of the distance to the nearest complex root. That
+/// distance shrinks to about sqrt(6(1 - e)) near periapsis, so `EllipseSolver::contour` precomputes
+/// the nodes for a ladder of radii halving down to that, and each solve picks one. f(z) is expanded
+/// about the centre at the nodes, so it doesn't cancel however small the radius. A solve is then a
+/// cube root, a square root, a logarithm, one sine and cosine and a fixed amount of arithmetic,
+/// with no iteration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contour<T = f64> {
+    /// Largest first, so the radius of the contour at index t is `CONTOUR_RADIUS_RATIO` / 2^t
+    radii: Vec<ContourRadius<T>>,
+    /// sqrt(6(1 - e)), the distance from periapsis to the nearest complex root
+    periapsis_root_distance: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ContourRadius<T> {
+    radius: T,
+    nodes: [ContourNode<T>; CONTOUR_NODES],
+}
+
+/// The parts of f(z) at a node that only depend on the radius, as (real, imaginary) pairs. With the
+/// node at z = c + h and h = rw, f(z) = f(c) + (1 - e cos(c)) h + e sin(c) (1 - cos(h)) + e cos(c) (h - sin(h))
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct ContourNode<T> {
+    direction: (T, T),
+    direction_squared: (T, T),
+    offset: (T, T),
+    one_minus_cos: (T, T),
+    offset_minus_sin: (T, T),
+}
+
+impl<T: Float> Contour<T> {
+    pub(crate) fn new(eccentricity: T) -> Self {
+        // 1 - e is clamped to its smallest value below e = 1, so that the table stays finite for
+        // eccentricities the solver rejects anyway
+        let one_minus_eccentricity = (T::from_f64(1.0) - eccentricity).max(T::EPSILON / T::from_f64(2.0));
+        let periapsis_root_distance = (T::from_f64(6.0) * one_minus_eccentricity).sqrt();
+        // Enough radii that the smallest is within the ratio of the distance at periapsis
+        let count = 1 + (-periapsis_root_distance.min(T::from_f64(1.0)).to_f64().log2()).ceil() as usize;
+        let radii = (0..count).map(|t| {
+            let radius = T::from_f64(CONTOUR_RADIUS_RATIO) / T::from_f64(2.0).powi(t as i32);
+            let nodes = std::array::from_fn(|k| {
+                // The nodes are offset by half a step so none of them land on the real axis, where f
+                // could be exactly zero
+                let angle = T::PI * (T::from_f64(k as f64) + T::from_f64(0.5)) / T::from_f64(CONTOUR_NODES as f64);
+                let direction = (angle.cos(), angle.sin());
+                let offset = (radius * direction.0, radius * direction.1);
+                // 1 - cos(h) = h^2 / 2! - h^4 / 4! + ... and h - sin(h) = h^3 / 3! - h^5 / 5! + ...
+                let offset_squared = complex_mul(offset, offset);
+                let (mut cos_series, mut sin_series) = ((T::from_f64(1.0), T::from_f64(0.0)), (T::from_f64(1.0), T::from_f64(0.0)));
+                for n in (1..=CONTOUR_SERIES_TERMS).rev() {
+                    let (cos_scale, sin_scale) = (T::from_f64(((2 * n + 1) * (2 * n + 2)) as f64), T::from_f64(((2 * n + 2) * (2 * n + 3)) as f64));
+                    let (cos_term, sin_term) = (complex_mul(offset_squared, cos_series), complex_mul(offset_squared, sin_series));
+                    cos_series = (T::from_f64(1.0) - cos_term.0 / cos_scale, -cos_term.1 / cos_scale);
+                    sin_series = (T::from_f64(1.0) - sin_term.0 / sin_scale, -sin_term.1 / sin_scale);
+                }
+                let one_minus_cos = complex_mul(offset_squared, cos_series);
+                let offset_minus_sin = complex_mul(complex_mul(offset_squared, offset), sin_series);
+                ContourNode {
+                    direction,
+                    direction_squared: complex_mul(direction, direction),
+                    offset,
+                    one_minus_cos: (one_minus_cos.0 / T::from_f64(2.0), one_minus_cos.1 / T::from_f64(2.0)),
+                    offset_minus_sin: (offset_minus_sin.0 / T::from_f64(6.0), offset_minus_sin.1 / T::from_f64(6.0)),
+                }
+            });
+            ContourRadius { radius, nodes }
+        }).collect();
+        Self { radii, periapsis_root_distance }
+    }
+}
+
+/// A table-driven method in the spirit of Fukushima's piecewise approximations
+/// (https://doi.org/10.1007/BF00053289). `EllipseSolver::piecewise` splits [0, pi] into segments
+/// equally spaced in the eccentric anomaly, and fits E(M) on each with a Chebyshev interpolant,
+/// which is close to the minimax polynomial. A solve is then a search of the segment boundaries, a
+/// polynomial evaluation and one fifth-order correction, with no iteration. Equal spacing in E puts short
+/// segments in M where E(M) is steep near periapsis. As e -> 1, E(M) there bends from linear to a
+/// cube root over a range of E that shrinks like sqrt(1 - e), so the first segment is split
+/// geometrically down to that scale, which keeps the interpolants close enough for one correction.
+/// The tolerances and iteration limit aren't used, though refinement steps still are
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Piecewise<T = f64> {
+    /// The mean anomaly at the start of each segment and at the end of the last
+    mean_anomaly_thresholds: Vec<T>,
+    /// Chebyshev coefficients of E(M) on each segment, with M mapped onto [-1, 1]
+    coefficients: Vec<[T; PIECEWISE_DEGREE + 1]>,
+}
+
+impl<T: Float> Piecewise<T> {
+    pub(crate) fn new(eccentricity: T) -> Self {
+        // The table is built in f64 regardless of T, since this only happens once
+        let ec = eccentricity.to_f64();
+        let solver = EllipseSolver::with_config(ec, SolverConfig::ellipse(Precision::Exact));
+        let first = std::f64::consts::PI / PIECEWISE_SEGMENTS as f64;
+        // 1 - e is clamped as in `Contour::new`, so that e >= 1 doesn't ask for endless halvings
+        let halvings = (first / (PIECEWISE_GRADING * (1.0 - ec).max(f64::EPSILON / 2.0).sqrt())).log2().ceil().max(0.0) as i32;
+        let thresholds: Vec<f64> = std::iter::once(0.0)
+            .chain((1..=halvings).rev().map(|halving| first / 2.0_f64.powi(halving)))
+            .chain((1..=PIECEWISE_SEGMENTS).map(|i| std::f64::consts::PI * i as f64 / PIECEWISE_SEGMENTS as f64))
+            .map(|eccentric_anomaly| eccentric_anomaly - ec * eccentric_anomaly.sin())
+            .collect();
+        let coefficients = thresholds.windows(2).map(|segment| {
+            let (start, end) = (segment[0], segment[1]);
+            // Interpolating at the Chebyshev nodes, which never include the segment boundaries
+            let angles: [f64; PIECEWISE_DEGREE + 1] = std::array::from_fn(|j| std::f64::consts::PI * (j as f64 + 0.5) / (PIECEWISE_DEGREE + 1) as f64);
+            let values = angles.map(|angle| solver.solve_unwrapped(start + (end - start) * (1.0 + angle.cos()) / 2.0));
+            std::array::from_fn(|k| {
+                let sum: f64 = angles.iter().zip(values).map(|(angle, value)| value * (k as f64 * angle).cos()).sum();
+                let scale = if k == 0 { 1.0 } else { 2.0 };
+                T::from_f64(scale * sum / (PIECEWISE_DEGREE + 1) as f64)
+            })
+        }).collect();
+        Self { mean_anomaly_thresholds: thresholds.into_iter().map(T::from_f64).collect(), coefficients }
+    }
+
+    /// The interpolant of E(M) on the segment containing `mean_anomaly`
+    fn interpolate(&self, mean_anomaly: T) -> T {
+        let segment = self.mean_anomaly_thresholds[1..self.coefficients.len()].partition_point(|threshold| *threshold <= mean_anomaly);
+        let (start, end) = (self.mean_anomaly_thresholds[segment], self.mean_anomaly_thresholds[segment + 1]);
+        let x = (T::from_f64(2.0) * mean_anomaly - start - end) / (end - start);
+        // Clenshaw's recurrence
+        let (mut b1, mut b2) = (T::from_f64(0.0), T::from_f64(0.0));
+        for coefficient in self.coefficients[segment][1..].iter().rev() {
+            (b1, b2) = (T::from_f64(2.0) * x * b1 - b2 + *coefficient, b1);
+        }
+        x * b1 - b2 + self.coefficients[segment][0]
+    }
+}
+
+/// Tommasini and Olivieri's ENRKE routine (https://doi.org/10.1051/0004-6361/202141423), which
+/// `Laguerre` borrows its seed from. The solver's range reduction already does ENRKE's. Near the
+/// singular corner of e -> 1 and M -> 0, where the seed is poor and the equation is nearly a
+/// cubic, the root is bracketed and bisected before iterating. Each correction is ENRKE's enhanced
+/// third-order step, which uses the first three derivatives of Kepler's equation. ENRKE follows
+/// the first one with Newton steps; taking the enhanced step every time costs no more
+/// trigonometry and only makes the later corrections more accurate. The iteration stops on ENRKE's
+/// a priori estimate of the error after a correction, e delta^2 / 2(1 - e cos(E)), which is a
+/// bound for a Newton step and so conservative for the enhanced one. Once that is below `tolerance`
+/// the final correction is applied, and the result is within `tolerance`, down to the limit set by
+/// the conditioning of the equation near periapsis. The configured tolerances aren't used, though
+/// the iteration limit and refinement steps still are
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Enrke<T = f64> {
+    tolerance: T,
+}
+
+impl<T: Float> Enrke<T> {
+    /// Tolerances that are below a couple of ulps of pi, or NaN, are raised to that
+    pub fn new(tolerance: T) -> Self {
+        Self { tolerance: tolerance.max(T::from_f64(ENRKE_MIN_TOLERANCE) * T::PI * T::EPSILON) }
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Laguerre {
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        seed(ec, mean_anomaly)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        laguerre_step(ec, mean_anomaly, eccentric_anomaly, sin, cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Newton {
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        seed(ec, mean_anomaly)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        let f = eccentric_anomaly - ec * sin - mean_anomaly;
+        -f / (T::from_f64(1.0) - ec * cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Halley {
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        seed(ec, mean_anomaly)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        let f = eccentric_anomaly - ec * sin - mean_anomaly;
+        let f_prime = T::from_f64(1.0) - ec * cos;
+        let f_prime_prime = ec * sin;
+        -f / (f_prime - f * f_prime_prime / (T::from_f64(2.0) * f_prime))
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Danby {
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        mean_anomaly + T::from_f64(DANBY_COEFFICIENT) * ec
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        quartic_delta(ec, mean_anomaly, eccentric_anomaly, sin, cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Mikkola {
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        let denominator = T::from_f64(4.0) * ec + T::from_f64(0.5);
+        let alpha = (T::from_f64(1.0) - ec) / denominator;
+        let beta = T::from_f64(0.5) * mean_anomaly / denominator;
+        if beta == T::from_f64(0.0) {
+            return mean_anomaly;
+        }
+        // s = z - alpha / z cancels for small M, so use the equivalent 2beta / (z^2 + alpha + alpha^2 / z^2)
+        let z = (beta + (beta.powi(2) + alpha.powi(3)).sqrt()).cbrt();
+        let s = T::from_f64(2.0) * beta / (z.powi(2) + alpha + alpha.powi(2) / z.powi(2));
+        // Mikkola's correction for the error of the cubic approximation
+        let s = s - T::from_f64(0.078) * s.powi(5) / (T::from_f64(1.0) + ec);
+        mean_anomaly + ec * (T::from_f64(3.0) * s - T::from_f64(4.0) * s.powi(3))
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        quartic_delta(ec, mean_anomaly, eccentric_anomaly, sin, cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Markley {
+    const NON_ITERATIVE: bool = true;
+
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        self.seed_with_trig(ec, mean_anomaly).0
+    }
+
+    fn seed_with_trig(&self, ec: T, mean_anomaly: T) -> (T, T, T) {
+        let eccentric_anomaly = markley_starter(ec, mean_anomaly);
+        let (sin, cos) = (eccentric_anomaly.sin(), eccentric_anomaly.cos());
+        let delta = fifth_order_delta(eccentric_anomaly - ec * sin - mean_anomaly, ec, sin, cos);
+        let (sin, cos) = rotate(sin, cos, delta);
+        (eccentric_anomaly + delta, sin, cos)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        fifth_order_delta(eccentric_anomaly - ec * sin - mean_anomaly, ec, sin, cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Contour<T> {
+    const NON_ITERATIVE: bool = true;
+
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        self.seed_with_trig(ec, mean_anomaly).0
+    }
+
+    fn seed_with_trig(&self, ec: T, mean_anomaly: T) -> (T, T, T) {
+        let one = T::from_f64(1.0);
+        let centre = markley_starter(ec, mean_anomaly);
+        let (sin, cos) = (centre.sin(), centre.cos());
+        let f = kepler_residual(ec, mean_anomaly, centre, sin);
+        // 1 - e cos(c) = (1 - e) + e (1 - cos(c)), which doesn't cancel near periapsis
+        let one_minus_cos = if cos > T::from_f64(0.0) { sin.powi(2) / (one + cos) } else { one - cos };
+        let f_prime = (one - ec) + ec * one_minus_cos;
+        let (ec_sin, ec_cos) = (ec * sin, ec * cos);
+        // Near periapsis the equation is close to the cubic (1 - e) E + E^3 / 6 = M, whose complex
+        // roots are about sqrt(3) E from the real one, or sqrt(6(1 - e)) at E = 0. Elsewhere they're
+        // at least about 1 away
+        let root_distance = self.periapsis_root_distance.max(T::from_f64(3.0).sqrt() * centre).min(one);
+        let index = (-root_distance.ln() / T::from_f64(2.0).ln()).to_f64().ceil() as usize;
+        let contour = &self.radii[index.min(self.radii.len() - 1)];
+        // With the lower half of the contour the conjugate of the upper half, the trapezoidal rule
+        // for both integrals reduces to sums of the real parts of w^n / f(z) over the upper half
+        let mut numerator = T::from_f64(0.0);
+        let mut denominator = T::from_f64(0.0);
+        for node in &contour.nodes {
+            let f = (
+                f + f_prime * node.offset.0 + ec_sin * node.one_minus_cos.0 + ec_cos * node.offset_minus_sin.0,
+                f_prime * node.offset.1 + ec_sin * node.one_minus_cos.1 + ec_cos * node.offset_minus_sin.1,
+            );
+            let norm = f.0.powi(2) + f.1.powi(2);
+            numerator += (node.direction_squared.0 * f.0 + node.direction_squared.1 * f.1) / norm;
+            denominator += (node.direction.0 * f.0 + node.direction.1 * f.1) / norm;
+        }
+        let delta = contour.radius * numerator / denominator;
+        let (sin, cos) = rotate(sin, cos, delta);
+        (centre + delta, sin, cos)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        Newton.delta(ec, mean_anomaly, eccentric_anomaly, sin, cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Piecewise<T> {
+    const NON_ITERATIVE: bool = true;
+
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        self.seed_with_trig(ec, mean_anomaly).0
+    }
+
+    fn seed_with_trig(&self, ec: T, mean_anomaly: T) -> (T, T, T) {
+        let eccentric_anomaly = self.interpolate(mean_anomaly);
+        let (sin, cos) = (eccentric_anomaly.sin(), eccentric_anomaly.cos());
+        let delta = self.delta(ec, mean_anomaly, eccentric_anomaly, sin, cos);
+        let (sin, cos) = rotate(sin, cos, delta);
+        (eccentric_anomaly + delta, sin, cos)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        // Near periapsis at eccentricities close to 1, the rounding error of the plain residual
+        // would be bigger than the tolerance once divided by the derivative
+        fifth_order_delta(kepler_residual(ec, mean_anomaly, eccentric_anomaly, sin), ec, sin, cos)
+    }
+}
+
+impl<T: Float> KeplerMethod<T> for Enrke<T> {
+    const APPLIES_FINAL_DELTA: bool = true;
+
+    fn seed(&self, ec: T, mean_anomaly: T) -> T {
+        if ec <= T::from_f64(ENRKE_CORNER_ECCENTRICITY) || mean_anomaly >= T::from_f64(ENRKE_CORNER_MEAN_ANOMALY) {
+            return seed(ec, mean_anomaly);
+        }
+        let mut lower = T::from_f64(ENRKE_CORNER_LOWER) * mean_anomaly;
+        let mut upper = T::from_f64(ENRKE_CORNER_UPPER);
+        for _ in 0..ENRKE_BISECTIONS {
+            let middle = (lower + upper) / T::from_f64(2.0);
+            if middle - ec * middle.sin() > mean_anomaly {
+                upper = middle;
+            } else {
+                lower = middle;
+            }
+        }
+        (lower + upper) / T::from_f64(2.0)
+    }
+
+    fn delta(&self, ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+        let c = T::from_f64;
+        let f = eccentric_anomaly - ec * sin - mean_anomaly;
+        let f_prime = c(1.0) - ec * cos;
+        let f_prime_prime = ec * sin;
+        let f_prime_prime_prime = ec * cos;
+        let f_prime_cubed = f_prime.powi(3);
+        -f / f_prime * (f_prime_cubed - c(0.5) * f * f_prime * f_prime_prime + f.powi(2) * f_prime_prime_prime / c(3.0))
+            / (f_prime_cubed - f * f_prime * f_prime_prime + c(0.5) * f.powi(2) * f_prime_prime_prime)
+    }
+
+    fn converged(&self, _: &SolverConfig<T>, delta: T, _: T, ec: T, cos: T) -> bool {
+        // e delta^2 / 2f' < tolerance, with ENRKE's guard against e = 0
+        delta.powi(2) * (ec + T::EPSILON) < T::from_f64(2.0) * self.tolerance * (T::from_f64(1.0) - ec * cos)
+    }
+}
+
+/// Markley's starter, the root of a cubic Pade approximation of Kepler's equation, which is within
+/// about 5e-4 radians of the true root, and a much smaller fraction of it near periapsis
+fn markley_starter<T: Float>(ec: T, mean_anomaly: T) -> T {
+    let pi = T::PI;
+    let one = T::from_f64(1.0);
+    let alpha = (T::from_f64(3.0) * pi.powi(2) + T::from_f64(MARKLEY_COEFFICIENT) * pi * (pi - mean_anomaly) / (one + ec))
+        / (pi.powi(2) - T::from_f64(6.0));
+    let d = T::from_f64(3.0) * (one - ec) + alpha * ec;
+    let q = T::from_f64(2.0) * alpha * d * (one - ec) - mean_anomaly.powi(2);
+    let r = T::from_f64(3.0) * alpha * d * (d - one + ec) * mean_anomaly + mean_anomaly.powi(3);
+    // r >= 0 for 0 <= M <= pi, and q^3 + r^2 >= 0 everywhere. The denominator is only zero at
+    // e = 1, M = 0, where r is too and the root is exactly 0
+    let w = (r + (q.powi(3) + r.powi(2)).sqrt()).cbrt().powi(2);
+    (T::from_f64(2.0) * r * w / (w.powi(2) + w * q + q.powi(2)).max(T::MIN_POSITIVE) + mean_anomaly) / d
+}
+
+fn complex_mul<T: Float>(a: (T, T), b: (T, T)) -> (T, T) {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+/// Danby and Burkardt's correction, which feeds each estimate of the step back into a Taylor
+/// expansion of Kepler's equation to get quartic convergence
+fn quartic_delta<T: Float>(ec: T, mean_anomaly: T, eccentric_anomaly: T, sin: T, cos: T) -> T {
+    let f = eccentric_anomaly - ec * sin - mean_anomaly;
+    let f_prime = T::from_f64(1.0) - ec * cos;
+    let f_prime_prime = ec * sin;
+    let f_prime_prime_prime = ec * cos;
+    let delta = -f / f_prime;
+    let delta = -f / (f_prime + delta * f_prime_prime / T::from_f64(2.0));
+    -f / (f_prime + delta * f_prime_prime / T::from_f64(2.0) + delta.powi(2) * f_prime_prime_prime / T::from_f64(6.0))
+}
+
+/// Markley's correction, which starts from a Halley step and takes the Taylor expansion of Kepler's
+/// equation one term further than `quartic_delta`. `f` is the residual E - e sin(E) - M, which is
+/// passed in so the caller can choose how carefully to evaluate it
+fn fifth_order_delta<T: Float>(f: T, ec: T, sin: T, cos: T) -> T {
+    // The derivative is only zero at e = 1, E = 0, where f is too, so this gives a zero step there
+    let f_prime = (T::from_f64(1.0) - ec * cos).max(T::MIN_POSITIVE);
+    let f_prime_prime = ec * sin;
+    let f_prime_prime_prime = ec * cos;
+    let delta = -f / (f_prime - f * f_prime_prime / (T::from_f64(2.0) * f_prime));
+    let delta = -f / (f_prime + delta * f_prime_prime / T::from_f64(2.0) + delta.powi(2) * f_prime_prime_prime / T::from_f64(6.0));
+    -f / (f_prime + delta * f_prime_prime / T::from_f64(2.0) + delta.powi(2) * f_prime_prime_prime / T::from_f64(6.0)
+        - delta.powi(3) * f_prime_prime / T::from_f64(24.0))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{bisection::bisection, config::{Precision, SolverConfig}, double_double::DoubleDouble, ellipse::EllipseSolver, error::SolveError, reference};
+
+    use super::{Danby, Enrke, Halley, KeplerMethod, Laguerre, Markley, Mikkola, Newton};
+
+    fn check_method<M: KeplerMethod<f64> + Copy>(method: M, max_iterations: usize) {
+        for e in [0.0, 0.1, 0.5, 0.9, 0.99, 0.999] {
+            let solver = EllipseSolver::with_method(e, method, SolverConfig::ellipse(Precision::Balanced));
+            let mean_anomalies: Vec<f64> = (-100..100).map(|x| x as f64 / 10.0).collect();
+            let mut out = vec![0.0; mean_anomalies.len()];
+            solver.solve_into(&mean_anomalies, &mut out);
+            for (m, out) in mean_anomalies.iter().zip(out) {
+                let reference = reference::solve_ellipse(e, *m);
+                let actual = solver.solve_unwrapped(*m);
+                let report = solver.solve_report(*m);
+                if (DoubleDouble::from(actual) - reference).abs().hi > 1.0e-9 || !report.converged || report.iterations > max_iterations {
+                    dbg!(reference.to_f64(), actual, report, e, m);
+                    panic!()
+                }
+                assert_eq!(out, solver.solve(*m));
+                let (eccentric_anomaly, sin, cos) = solver.solve_with_trig(*m);
+                assert!((sin - eccentric_anomaly.sin()).abs() < 1.0e-15 && (cos - eccentric_anomaly.cos()).abs() < 1.0e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn test_methods() {
+        check_method(Laguerre, 5);
+        check_method(Newton, 10);
+        check_method(Halley, 6);
+        check_method(Danby, 6);
+        check_method(Mikkola, 3);
+        check_method(Markley, 1);
+        let solver = EllipseSolver::with_method(0.7, Laguerre, SolverConfig::ellipse(Precision::Balanced));
+        for x in -100..100 {
+            let m = x as f64 / 10.0;
+            assert_eq!(solver.solve(m), EllipseSolver::new(0.7).solve(m));
+        }
+    }
+
+    #[test]
+    fn test_contour() {
+        let mean_anomalies: Vec<f64> = (-1000..1000).map(|x| x as f64 / 100.0)
+            .chain((0..160).map(|x| f64::powf(10.0, -x as f64 / 8.0)))
+            .collect();
+        for e in [0.0, 0.1, 0.5, 0.7, 0.9, 0.99, 0.999, 0.9999, 1.0 - 1.0e-6, 1.0 - 1.0e-9, 1.0 - 1.0e-12, 1.0 - f64::EPSILON] {
+            let solver = EllipseSolver::contour(e, SolverConfig::ellipse(Precision::Balanced));
+            let mut out = vec![0.0; mean_anomalies.len()];
+            solver.solve_into(&mean_anomalies, &mut out);
+            for (m, out) in mean_anomalies.iter().zip(out) {
+                let reference = reference::solve_ellipse(e, *m);
+                let report = solver.solve_report(*m);
+                // The quadrature alone is good to rounding, with no iteration
+                if (DoubleDouble::from(solver.solve_unwrapped(*m)) - reference).abs().hi > 4.0 * f64::EPSILON * reference.to_f64().abs().max(1.0) || !report.converged || report.iterations != 1 {
+                    dbg!(reference.to_f64(), report, e, m);
+                    panic!()
+                }
+                assert_eq!(out, solver.solve(*m));
+                let (eccentric_anomaly, sin, cos) = solver.solve_with_trig(*m);
+                assert!((sin - eccentric_anomaly.sin()).abs() < 1.0e-15 && (cos - eccentric_anomaly.cos()).abs() < 1.0e-15);
+            }
+        }
+        let f32_solver = EllipseSolver::contour(0.9_f32, SolverConfig::ellipse(Precision::Balanced));
+        assert!((f32_solver.solve(1.0) as f64 - EllipseSolver::new(0.9).solve(1.0)).abs() < 1.0e-5);
+        // Parabolic and hyperbolic eccentricities are rejected, but building the contours for them
+        // mustn't blow up
+        for e in [1.0, 1.5] {
+            let solver = EllipseSolver::contour(e, SolverConfig::ellipse(Precision::Balanced));
+            solver.solve(1.0);
+            assert_eq!(solver.try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: e }));
+        }
+        EllipseSolver::contour(1.0_f32, SolverConfig::ellipse(Precision::Balanced)).solve(1.0);
+    }
+
+    #[test]
+    fn test_piecewise() {
+        let mean_anomalies: Vec<f64> = (-1000..1000).map(|x| x as f64 / 100.0)
+            .chain((0..160).map(|x| f64::powf(10.0, -x as f64 / 8.0)))
+            .collect();
+        for e in [0.0, 0.1, 0.5, 0.8, 0.9, 0.99, 0.999, 0.9999, 1.0 - 1.0e-6, 1.0 - 1.0e-9, 1.0 - 1.0e-12, 1.0 - f64::EPSILON] {
+            let solver = EllipseSolver::piecewise(e, SolverConfig::ellipse(Precision::Balanced));
+            let mut out = vec![0.0; mean_anomalies.len()];
+            solver.solve_into(&mean_anomalies, &mut out);
+            for (m, out) in mean_anomalies.iter().zip(out) {
+                let reference = reference::solve_ellipse(e, *m);
+                let report = solver.solve_report(*m);
+                // One correction, with no iteration
+                if (DoubleDouble::from(solver.solve_unwrapped(*m)) - reference).abs().hi > 1.0e-9 || !report.converged || report.iterations != 1 {
+                    dbg!(reference.to_f64(), report, e, m);
+                    panic!()
+                }
+                assert_eq!(out, solver.solve(*m));
+            }
+        }
+        // Near periapsis as e -> 1, where E(M) is closest to a cube root
+        for e in [0.9, 0.99, 0.9999, 0.999999, 1.0 - 1.0e-9] {
+            let solver = EllipseSolver::piecewise(e, SolverConfig::ellipse(Precision::Balanced));
+            for m in [1.0e-12, 1.0e-10, 1.0e-8, 1.0e-6, 1.0e-4] {
+                let report = solver.solve_report(m);
+                assert!(report.iterations == 1 && (DoubleDouble::from(report.anomaly) - reference::solve_ellipse(e, m)).abs().hi < 1.0e-9);
+            }
+        }
+        let f32_solver = EllipseSolver::piecewise(0.5_f32, SolverConfig::ellipse(Precision::Balanced));
+        assert!((f32_solver.solve(1.0) as f64 - EllipseSolver::new(0.5).solve(1.0)).abs() < 1.0e-5);
+        // Parabolic and hyperbolic eccentricities are rejected, but building the table for them
+        // mustn't blow up
+        for e in [1.0, 1.5] {
+            let solver = EllipseSolver::piecewise(e, SolverConfig::ellipse(Precision::Balanced));
+            solver.solve(1.0);
+            assert_eq!(solver.try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: e }));
+        }
+        EllipseSolver::piecewise(1.0_f32, SolverConfig::ellipse(Precision::Balanced)).solve(1.0);
+    }
+
+    #[test]
+    fn test_enrke() {
+        // Include mean anomalies down into the corner near periapsis
+        let mean_anomalies: Vec<f64> = (-314..=314).map(|x| x as f64 / 100.0)
+            .chain((0..100).map(|x| f64::powf(10.0, -x as f64 / 8.0)))
+            .collect();
+        for tolerance in [1.0e-3, 1.0e-6, 1.0e-10, 1.0e-13, 0.0, -1.0, f64::NAN] {
+            // Tolerances below a couple of ulps of pi are raised to that, but near periapsis the
+            // error is then limited by the conditioning of the equation
+            let raised = Enrke::new(tolerance).tolerance;
+            assert!(raised == tolerance || raised == 4.0 * std::f64::consts::PI * f64::EPSILON);
+            let expected = tolerance.max(1.0e-13);
+            for e in [0.0, 0.5, 0.9, 0.99, 0.999, 0.9999] {
+                let solver = EllipseSolver::with_method(e, Enrke::new(tolerance), SolverConfig::ellipse(Precision::Balanced));
+                let mut out = vec![0.0; mean_anomalies.len()];
+                solver.solve_into(&mean_anomalies, &mut out);
+                for (m, out) in mean_anomalies.iter().zip(out) {
+                    let reference = reference::solve_ellipse(e, *m);
+                    let report = solver.solve_report(*m);
+                    // The most iterations are just outside the corner, where the seed is worst
+                    if (DoubleDouble::from(solver.solve_unwrapped(*m)) - reference).abs().hi > expected + 4.0 * f64::EPSILON || !report.converged || report.iterations > 6 {
+                        dbg!(reference.to_f64(), report, e, m, tolerance);
+                        panic!()
+                    }
+                    assert_eq!(out, solver.solve(*m));
+                    let (eccentric_anomaly, sin, cos) = solver.solve_with_trig(*m);
+                    assert!((sin - eccentric_anomaly.sin()).abs() < 1.0e-15 && (cos - eccentric_anomaly.cos()).abs() < 1.0e-15);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mikkola_seed() {
+        for x in 0..=100 {
+            let e = x as f64 / 100.0;
+            for y in 0..=314 {
+                let m = y as f64 / 100.0;
+                let reference = reference::solve_ellipse(e, m).to_f64();
+                assert!((Mikkola.seed(e, m) - reference).abs() < 4.0e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_markley() {
+        // Same grid as test_ellipse, but Markley's method should be good to rounding everywhere with
+        // no iteration at all
+        for x in 1..999 {
+            let e = x as f64 / 1000.0;
+            let solver = EllipseSolver::with_method(e, Markley, SolverConfig::ellipse(Precision::Balanced));
+            for y in 0..6283 {
+                let m = y as f64 / 1000.0;
+                let expected = bisection(&|eccentric_anomaly: f64| eccentric_anomaly - m - e * eccentric_anomaly.sin(), -100000.0, 100000.0);
+                let report = solver.solve_report(m);
+                let difference = (expected - solver.solve_unwrapped(m)).abs();
+                if difference > 1.0e-12 || report.iterations != 1 {
+                    dbg!(expected, report, e, m);
+                    panic!()
+                }
+            }
+        }
+        // The cubic is 0/0 at e = 1, M = 0
+        assert_eq!(Markley.seed_with_trig(1.0, 0.0), (0.0, 0.0, 1.0));
+        let exact = EllipseSolver::with_method(0.5, Markley, SolverConfig::ellipse(Precision::Exact));
+        assert_eq!(exact.solve_report(1.0).iterations, 3);
+    }
+}
@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{elements::OrbitalElements, error::SolveError, float::Float, kepler::{Anomaly, KeplerSolver}, vector::{add, scale}};
+
+/// A Keplerian orbit around a body with gravitational parameter mu, which gives the position and
+/// velocity at any time. The solver and the rotation from the perifocal frame are built once in
+/// `new`, so querying many times is cheap. The perifocal frame has x towards periapsis and z along
+/// the angular momentum.
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::{elements::OrbitalElements, orbit::Orbit};
+///
+/// fn example_orbit() {
+///     let elements = OrbitalElements::from_semi_major_axis(7.0e6, 0.1, 0.5, 1.0, 2.0, 0.0, 0.0);
+///     let orbit = Orbit::new(elements, 3.986e14);
+///     let (position, velocity) = orbit.state_at(600.0);
+///     println!("{:?} {:?}", position, velocity);
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Orbit<T = f64> {
+    elements: OrbitalElements<T>,
+    gravitational_parameter: T,
+    mean_motion: T,
+    solver: KeplerSolver<T>,
+    // The perifocal x and y axes in the inertial frame
+    periapsis_direction: [T; 3],
+    semi_latus_rectum_direction: [T; 3],
+}
+
+impl<T: Float> Orbit<T> {
+    pub fn new(elements: OrbitalElements<T>, gravitational_parameter: T) -> Self {
+        let solver = KeplerSolver::new(elements.eccentricity);
+        let mean_motion = match solver {
+            // Barker's equation has its own mean anomaly convention, sqrt(mu / (2 q^3)) * (t - T)
+            KeplerSolver::Parabola(_) => T::sqrt(gravitational_parameter / (T::from_f64(2.0) * elements.periapsis().powi(3))),
+            _ => T::sqrt(gravitational_parameter / elements.semi_major_axis().abs().powi(3)),
+        };
+
+        let (sin_node, cos_node) = (elements.longitude_of_ascending_node.sin(), elements.longitude_of_ascending_node.cos());
+        let (sin_inclination, cos_inclination) = (elements.inclination.sin(), elements.inclination.cos());
+        let (sin_periapsis, cos_periapsis) = (elements.argument_of_periapsis.sin(), elements.argument_of_periapsis.cos());
+        let periapsis_direction = [
+            cos_node * cos_periapsis - sin_node * sin_periapsis * cos_inclination,
+            sin_node * cos_periapsis + cos_node * sin_periapsis * cos_inclination,
+            sin_periapsis * sin_inclination,
+        ];
+        let semi_latus_rectum_direction = [
+            -cos_node * sin_periapsis - sin_node * cos_periapsis * cos_inclination,
+            -sin_node * sin_periapsis + cos_node * cos_periapsis * cos_inclination,
+            cos_periapsis * sin_inclination,
+        ];
+
+        Self { elements, gravitational_parameter, mean_motion, solver, periapsis_direction, semi_latus_rectum_direction }
+    }
+
+    /// Builds the orbit that passes through `position` with `velocity` at time `epoch`, see
+    /// `OrbitalElements::from_state`
+    pub fn from_state(position: [T; 3], velocity: [T; 3], gravitational_parameter: T, epoch: T) -> Result<Self, SolveError> {
+        OrbitalElements::from_state(position, velocity, gravitational_parameter, epoch)
+            .map(|elements| Self::new(elements, gravitational_parameter))
+    }
+
+    pub fn elements(&self) -> &OrbitalElements<T> {
+        &self.elements
+    }
+
+    pub fn gravitational_parameter(&self) -> T {
+        self.gravitational_parameter
+    }
+
+    /// In the convention of the solver for this orbit, see `OrbitalElements`
+    pub fn mean_motion(&self) -> T {
+        self.mean_motion
+    }
+
+    pub fn mean_anomaly_at(&self, time: T) -> T {
+        self.elements.mean_anomaly_at_epoch + self.mean_motion * (time - self.elements.epoch)
+    }
+
+    /// Returns the time it takes to go from `from_true_anomaly` to `to_true_anomaly`. On elliptic
+    /// orbits this is the time until the body first reaches `to_true_anomaly`, plus `revolutions`
+    /// periods, so it's never negative. On open orbits it's negative if the body passes
+    /// `to_true_anomaly` first, and there are no revolutions to add, so `revolutions` must be 0.
+    /// Returns an error if either true anomaly is beyond the asymptote of an open orbit, or if
+    /// `revolutions` isn't 0 on an open orbit
+    pub fn time_of_flight(&self, from_true_anomaly: T, to_true_anomaly: T, revolutions: usize) -> Result<T, SolveError> {
+        let eccentricity = self.elements.eccentricity;
+        let from = Anomaly::from_true(eccentricity, from_true_anomaly)?.to_mean(eccentricity);
+        let to = Anomaly::from_true(eccentricity, to_true_anomaly)?;
+        self.mean_anomaly_difference_to_time(to, to.to_mean(eccentricity) - from, revolutions)
+    }
+
+    /// Returns the time from `time` until the body reaches `true_anomaly`, see `time_of_flight`
+    pub fn time_until_true_anomaly(&self, time: T, true_anomaly: T, revolutions: usize) -> Result<T, SolveError> {
+        let eccentricity = self.elements.eccentricity;
+        let to = Anomaly::from_true(eccentricity, true_anomaly)?;
+        self.mean_anomaly_difference_to_time(to, to.to_mean(eccentricity) - self.mean_anomaly_at(time), revolutions)
+    }
+
+    fn mean_anomaly_difference_to_time(&self, to: Anomaly<T>, difference: T, revolutions: usize) -> Result<T, SolveError> {
+        match to {
+            Anomaly::Eccentric(_) => {
+                // The time until the next time the body gets there, so reduce into [0, 2pi)
+                let mut difference = difference - T::TAU * (difference / T::TAU).floor();
+                if difference >= T::TAU {
+                    difference = T::from_f64(0.0);
+                }
+                Ok((difference + T::TAU * T::from_f64(revolutions as f64)) / self.mean_motion)
+            }
+            _ if revolutions == 0 => Ok(difference / self.mean_motion),
+            _ => Err(SolveError::RevolutionsOnOpenOrbit { eccentricity: self.elements.eccentricity.to_f64(), revolutions }),
+        }
+    }
+
+    /// Returns the position and velocity in the inertial frame
+    pub fn state_at(&self, time: T) -> ([T; 3], [T; 3]) {
+        self.to_inertial(self.perifocal_state_at(time))
+    }
+
+    /// Returns the position and velocity in the perifocal frame, where z is always 0
+    pub fn perifocal_state_at(&self, time: T) -> ([T; 3], [T; 3]) {
+        let mean_anomaly = self.mean_anomaly_at(time);
+        match &self.solver {
+            KeplerSolver::Ellipse(solver) => self.perifocal_state_elliptic(solver.solve_with_trig(mean_anomaly)),
+            KeplerSolver::Parabola(solver) => self.perifocal_state_true(Anomaly::Parabolic(solver.solve(mean_anomaly)).to_true(self.elements.eccentricity)),
+            KeplerSolver::Hyperbola(solver) => self.perifocal_state_hyperbolic(solver.solve_with_trig(mean_anomaly)),
+        }
+    }
+
+    /// Same as `state_at`, but returns an error rather than a garbage value if the elements are
+    /// invalid or the solver fails to converge
+    pub fn try_state_at(&self, time: T) -> Result<([T; 3], [T; 3]), SolveError> {
+        self.try_perifocal_state_at(time).map(|state| self.to_inertial(state))
+    }
+
+    /// Same as `perifocal_state_at`, but returns an error rather than a garbage value if the
+    /// elements are invalid or the solver fails to converge
+    pub fn try_perifocal_state_at(&self, time: T) -> Result<([T; 3], [T; 3]), SolveError> {
+        if !self.elements.semi_latus_rectum.is_finite() || !self.gravitational_parameter.is_finite() || !time.is_finite() {
+            return Err(SolveError::NonFiniteInput);
+        }
+        // The solvers check the eccentricity and the mean anomaly, and the trig values are cheap
+        // enough to recompute here
+        let eccentricity = self.elements.eccentricity;
+        Ok(match self.solver.try_solve(self.mean_anomaly_at(time))? {
+            Anomaly::Eccentric(eccentric_anomaly) => self.perifocal_state_elliptic((eccentric_anomaly, eccentric_anomaly.sin(), eccentric_anomaly.cos())),
+            anomaly @ Anomaly::Parabolic(_) => self.perifocal_state_true(anomaly.to_true(eccentricity)),
+            Anomaly::Hyperbolic(hyperbolic_anomaly) => self.perifocal_state_hyperbolic((hyperbolic_anomaly, hyperbolic_anomaly.sinh(), hyperbolic_anomaly.cosh())),
+        })
+    }
+
+    fn perifocal_state_elliptic(&self, (_, sin, cos): (T, T, T)) -> ([T; 3], [T; 3]) {
+        let one = T::from_f64(1.0);
+        let a = self.elements.semi_major_axis();
+        let e = self.elements.eccentricity;
+        let sqrt_one_minus_e2 = T::sqrt((one - e) * (one + e));
+        let radius = a * (one - e * cos);
+        let speed_factor = T::sqrt(self.gravitational_parameter * a) / radius;
+        let position = [a * (cos - e), a * sqrt_one_minus_e2 * sin, T::from_f64(0.0)];
+        let velocity = [-speed_factor * sin, speed_factor * sqrt_one_minus_e2 * cos, T::from_f64(0.0)];
+        (position, velocity)
+    }
+
+    fn perifocal_state_hyperbolic(&self, (_, sinh, cosh): (T, T, T)) -> ([T; 3], [T; 3]) {
+        let one = T::from_f64(1.0);
+        let a = -self.elements.semi_major_axis();
+        let e = self.elements.eccentricity;
+        let sqrt_e2_minus_one = T::sqrt((e - one) * (e + one));
+        let radius = a * (e * cosh - one);
+        let speed_factor = T::sqrt(self.gravitational_parameter * a) / radius;
+        let position = [a * (e - cosh), a * sqrt_e2_minus_one * sinh, T::from_f64(0.0)];
+        let velocity = [-speed_factor * sinh, speed_factor * sqrt_e2_minus_one * cosh, T::from_f64(0.0)];
+        (position, velocity)
+    }
+
+    /// Used for (near-)parabolic orbits, where a is infinite or close to it so the formulas in terms
+    /// of a lose all their precision
+    fn perifocal_state_true(&self, true_anomaly: T) -> ([T; 3], [T; 3]) {
+        let p = self.elements.semi_latus_rectum;
+        let e = self.elements.eccentricity;
+        let (sin, cos) = (true_anomaly.sin(), true_anomaly.cos());
+        let radius = p / (T::from_f64(1.0) + e * cos);
+        let speed_factor = T::sqrt(self.gravitational_parameter / p);
+        let position = [radius * cos, radius * sin, T::from_f64(0.0)];
+        let velocity = [-speed_factor * sin, speed_factor * (e + cos), T::from_f64(0.0)];
+        (position, velocity)
+    }
+
+    fn to_inertial(&self, (position, velocity): ([T; 3], [T; 3])) -> ([T; 3], [T; 3]) {
+        let rotate = |v: [T; 3]| add(scale(self.periapsis_direction, v[0]), scale(self.semi_latus_rectum_direction, v[1]));
+        (rotate(position), rotate(velocity))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use crate::{elements::OrbitalElements, error::SolveError};
+
+    use super::Orbit;
+
+    const MU: f64 = 3.986_004_418e14;
+
+    fn norm(v: [f64; 3]) -> f64 {
+        (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+    }
+
+    fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+        [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+    }
+
+    fn orbits() -> Vec<Orbit> {
+        let mut orbits = vec![];
+        for e in [0.0, 0.3, 0.9, 0.999_999_5, 1.0, 1.000_000_5, 1.5, 10.0] {
+            for (i, node, periapsis) in [(0.0, 0.0, 0.0), (0.5, 1.0, 2.0), (3.0, -1.0, 4.0)] {
+                let elements = OrbitalElements { semi_latus_rectum: 1.0e7, eccentricity: e, inclination: i, longitude_of_ascending_node: node, argument_of_periapsis: periapsis, mean_anomaly_at_epoch: 0.3, epoch: 100.0 };
+                orbits.push(Orbit::new(elements, MU));
+            }
+        }
+        orbits
+    }
+
+    #[test]
+    fn test_orbit_conserves_energy_and_angular_momentum() {
+        for orbit in orbits() {
+            let elements = orbit.elements();
+            let energy = -MU * (1.0 - elements.eccentricity.powi(2)) / (2.0 * elements.semi_latus_rectum);
+            let angular_momentum = (MU * elements.semi_latus_rectum).sqrt();
+            for x in -100..100 {
+                let (position, velocity) = orbit.state_at(x as f64 * 100.0);
+                let actual_energy = norm(velocity).powi(2) / 2.0 - MU / norm(position);
+                let actual_angular_momentum = norm(cross(position, velocity));
+                assert!((actual_energy - energy).abs() < 1.0e-9 * (MU / norm(position) + energy.abs()), "{:?} {}", elements, x);
+                assert!((actual_angular_momentum - angular_momentum).abs() < 1.0e-9 * angular_momentum, "{:?} {}", elements, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_orbit_velocity_is_derivative_of_position() {
+        for orbit in orbits() {
+            for x in -10..10 {
+                let t = x as f64 * 1000.0;
+                let h = 0.1;
+                let (position_before, _) = orbit.state_at(t - h);
+                let (position_after, _) = orbit.state_at(t + h);
+                let (_, velocity) = orbit.state_at(t);
+                for i in 0..3 {
+                    let derivative = (position_after[i] - position_before[i]) / (2.0 * h);
+                    assert!((derivative - velocity[i]).abs() < 1.0e-4 * norm(velocity), "{:?} {}", orbit.elements(), t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_orbit_periapsis_at_epoch() {
+        for orbit in orbits() {
+            let elements = OrbitalElements { mean_anomaly_at_epoch: 0.0, ..*orbit.elements() };
+            let orbit = Orbit::new(elements, MU);
+            let (perifocal_position, _) = orbit.perifocal_state_at(elements.epoch);
+            assert!((perifocal_position[0] - elements.periapsis()).abs() < 1.0e-9 * elements.periapsis());
+            assert!(perifocal_position[1].abs() < 1.0e-9 * elements.periapsis());
+            let (position, velocity) = orbit.state_at(elements.epoch);
+            // The node is where z crosses 0 going up, so the periapsis is at height r sin(i) sin(w)
+            let expected_z = elements.periapsis() * elements.inclination.sin() * elements.argument_of_periapsis.sin();
+            assert!((position[2] - expected_z).abs() < 1.0e-9 * elements.periapsis());
+            // At periapsis the velocity is perpendicular to the position
+            let dot = position[0] * velocity[0] + position[1] * velocity[1] + position[2] * velocity[2];
+            assert!(dot.abs() < 1.0e-9 * norm(position) * norm(velocity));
+        }
+    }
+
+    #[test]
+    fn test_orbit_is_periodic() {
+        let elements = OrbitalElements::from_semi_major_axis(7.0e6, 0.3, 0.5, 1.0, 2.0, 0.0, 0.0);
+        let orbit = Orbit::new(elements, MU);
+        let period = 2.0 * PI / orbit.mean_motion();
+        let (position, velocity) = orbit.state_at(1234.0);
+        let (position_later, velocity_later) = orbit.state_at(1234.0 + 10.0 * period);
+        for i in 0..3 {
+            assert!((position[i] - position_later[i]).abs() < 1.0e-6 * norm(position));
+            assert!((velocity[i] - velocity_later[i]).abs() < 1.0e-6 * norm(velocity));
+        }
+    }
+
+    fn perifocal_true_anomaly_error(orbit: &Orbit, time: f64, expected: f64) -> f64 {
+        let (position, _) = orbit.perifocal_state_at(time);
+        let difference = f64::atan2(position[1], position[0]) - expected;
+        (difference - 2.0 * PI * (difference / (2.0 * PI)).round()).abs()
+    }
+
+    #[test]
+    fn test_orbit_time_of_flight() {
+        for orbit in orbits() {
+            let closed = orbit.elements().eccentricity < 1.0 - 1.0e-6;
+            let limit = if closed { PI } else { f64::acos(-1.0 / orbit.elements().eccentricity).min(PI) - 0.01 };
+            for x in -10..10 {
+                let from = x as f64 / 10.0 * limit;
+                for y in -10..10 {
+                    let to = y as f64 / 10.0 * limit;
+                    let time_of_flight = orbit.time_of_flight(from, to, 0).unwrap();
+                    if closed {
+                        assert!(time_of_flight >= 0.0);
+                    }
+                    // Start at `from` and check we end up at `to`
+                    let start = orbit.time_until_true_anomaly(0.0, from, 0).unwrap();
+                    assert!(perifocal_true_anomaly_error(&orbit, start, from) < 1.0e-9, "{:?} {}", orbit.elements(), from);
+                    assert!(perifocal_true_anomaly_error(&orbit, start + time_of_flight, to) < 1.0e-9, "{:?} {} {}", orbit.elements(), from, to);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_orbit_time_of_flight_wraps() {
+        let orbit = Orbit::new(OrbitalElements::from_semi_major_axis(7.0e6, 0.3, 0.5, 1.0, 2.0, 0.0, 0.0), MU);
+        let period = 2.0 * PI / orbit.mean_motion();
+        assert_eq!(orbit.time_of_flight(1.0, 1.0, 0), Ok(0.0));
+        assert!((orbit.time_of_flight(1.0, 1.0, 3).unwrap() - 3.0 * period).abs() < 1.0e-9 * period);
+        let there = orbit.time_of_flight(1.0, 2.0, 0).unwrap();
+        let back = orbit.time_of_flight(2.0, 1.0, 0).unwrap();
+        assert!((there + back - period).abs() < 1.0e-9 * period);
+        assert!((orbit.time_of_flight(1.0 + 2.0 * PI, 2.0 - 4.0 * PI, 1).unwrap() - there - period).abs() < 1.0e-9 * period);
+        assert!((orbit.time_until_true_anomaly(period * 1000.0, 2.0, 0).unwrap() - orbit.time_until_true_anomaly(0.0, 2.0, 0).unwrap()).abs() < 1.0e-6 * period);
+
+        let hyperbola = Orbit::new(OrbitalElements::from_semi_major_axis(-7.0e6, 2.0, 0.5, 1.0, 2.0, 0.0, 0.0), MU);
+        assert!(hyperbola.time_of_flight(1.0, -1.0, 0).unwrap() < 0.0);
+        assert_eq!(hyperbola.time_of_flight(1.0, 3.0, 0), Err(SolveError::BeyondAsymptote { eccentricity: 2.0, true_anomaly: 3.0 }));
+        assert_eq!(hyperbola.time_of_flight(1.0, -1.0, 1), Err(SolveError::RevolutionsOnOpenOrbit { eccentricity: 2.0, revolutions: 1 }));
+    }
+
+    #[test]
+    fn test_orbit_try_state_at() {
+        for orbit in orbits() {
+            let (position, velocity) = orbit.state_at(1234.0);
+            let (try_position, try_velocity) = orbit.try_state_at(1234.0).unwrap();
+            for i in 0..3 {
+                assert!((position[i] - try_position[i]).abs() < 1.0e-12 * norm(position));
+                assert!((velocity[i] - try_velocity[i]).abs() < 1.0e-12 * norm(velocity));
+            }
+        }
+        let orbit = Orbit::new(OrbitalElements::from_semi_major_axis(7.0e6, 0.3, 0.0, 0.0, 0.0, 0.0, 0.0), MU);
+        assert_eq!(orbit.try_state_at(f64::NAN), Err(SolveError::NonFiniteInput));
+        let orbit = Orbit::new(OrbitalElements::from_semi_major_axis(7.0e6, -0.3, 0.0, 0.0, 0.0, 0.0, 0.0), MU);
+        assert_eq!(orbit.try_state_at(0.0), Err(SolveError::EccentricityOutOfRange { eccentricity: -0.3 }));
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::SolveError, float::Float};
+
+/// Solves Barker's equation, M = D + D^3 / 3, where D = tan(true anomaly / 2) is the parabolic
+/// anomaly and M = sqrt(mu / (2 q^3)) * (t - T) is the parabolic mean anomaly
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::parabola::ParabolaSolver;
+///
+/// fn example_parabola() {
+///     let solver = ParabolaSolver::new();
+///     println!("{}", solver.solve(1.2));
+///     println!("{}", solver.solve_true_anomaly(100.0));
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParabolaSolver;
+
+impl ParabolaSolver {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Works with all values of mean anomaly, returns the parabolic anomaly D
+    pub fn solve<T: Float>(&self, mean_anomaly: T) -> T {
+        // Barker's equation is a depressed cubic, D^3 + 3D - 3M = 0, which has exactly one real root
+        // Substituting D = 2sinh(x) turns it into 2sinh(3x) = 3M, so there's no need for Cardano's
+        // formula (which loses all its precision to cancellation for small M)
+        T::from_f64(2.0) * T::sinh(T::asinh(T::from_f64(1.5) * mean_anomaly) / T::from_f64(3.0))
+    }
+
+    /// Works with all values of mean anomaly, returns an error rather than a garbage value if the
+    /// input is not finite
+    pub fn try_solve<T: Float>(&self, mean_anomaly: T) -> Result<T, SolveError> {
+        if !mean_anomaly.is_finite() {
+            return Err(SolveError::NonFiniteInput);
+        }
+        Ok(self.solve(mean_anomaly))
+    }
+
+    /// Works with all values of mean anomaly, returns the true anomaly in (-pi, pi)
+    pub fn solve_true_anomaly<T: Float>(&self, mean_anomaly: T) -> T {
+        T::from_f64(2.0) * T::atan(self.solve(mean_anomaly))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{bisection::bisection, error::SolveError};
+
+    use super::ParabolaSolver;
+
+    fn solve_with_bisection(m: f64) -> f64 {
+        // We don't care about speed here, so just use as wide a range as possible
+        let f = |parabolic_anomaly: f64| parabolic_anomaly + parabolic_anomaly.powi(3) / 3.0 - m;
+        bisection(&f, -100000.0, 100000.0)
+    }
+
+    #[test]
+    fn test_parabola() {
+        let mean_anomalies: Vec<f64> = (-10000..10000)
+            .map(|x| f64::powi(x as f64, 3) / 1.0e6)
+            .collect();
+
+        let solver = ParabolaSolver::new();
+        for m in &mean_anomalies {
+            let expected = solve_with_bisection(*m);
+            let actual = solver.solve(*m);
+            let difference = if actual.abs() < 1.0e-5 { expected - actual } else { (expected - actual) / actual }.abs();
+            if difference > 1.0e-10 {
+                dbg!(expected, actual, difference, m);
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_parabola_true_anomaly() {
+        let solver = ParabolaSolver::new();
+        for x in -1000..1000 {
+            let true_anomaly = x as f64 / 1000.0 * 3.1;
+            let d = f64::tan(true_anomaly / 2.0);
+            let m = d + d.powi(3) / 3.0;
+            let actual = solver.solve_true_anomaly(m);
+            if (actual - true_anomaly).abs() > 1.0e-10 {
+                dbg!(true_anomaly, actual, m);
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_parabola_try_solve() {
+        let solver = ParabolaSolver::new();
+        assert_eq!(solver.try_solve(1.0), Ok(solver.solve(1.0)));
+        assert_eq!(solver.try_solve(f64::NAN), Err(SolveError::NonFiniteInput));
+        assert_eq!(solver.try_solve(f64::INFINITY), Err(SolveError::NonFiniteInput));
+    }
+}
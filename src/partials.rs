@@ -0,0 +1,22 @@
+//! Derivatives of the solution of Kepler's equation, found by implicitly differentiating the
+//! equation rather than the iteration, so they're exact for the returned anomaly however close to
+//! the threshold the iteration stopped
+
+use serde::{Deserialize, Serialize};
+
+/// First derivatives of an eccentric or hyperbolic anomaly
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Partials<T = f64> {
+    /// With respect to the mean anomaly
+    pub d_mean_anomaly: T,
+    /// With respect to the eccentricity
+    pub d_eccentricity: T,
+}
+
+/// Second derivatives of an eccentric or hyperbolic anomaly
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SecondPartials<T = f64> {
+    pub d2_mean_anomaly: T,
+    pub d2_mean_anomaly_eccentricity: T,
+    pub d2_eccentricity: T,
+}
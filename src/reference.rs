@@ -0,0 +1,126 @@
+//! Reference solutions of Kepler's equation in double-double arithmetic, for measuring the error of
+//! the fast solvers and generating golden values. These are far too slow for anything else.
+//!
+//! The f64 solver's answer is refined with Newton's method in double-double until the steps stop
+//! changing it. Since each step roughly doubles the number of correct bits, two or three steps
+//! take the result from f64 to double-double precision, and the error left over is dominated by
+//! the conditioning of the equation rather than the iteration. The eccentricity and mean anomaly
+//! are taken as exact f64 values.
+
+use crate::{config::MAX_ITERATIONS, double_double::DoubleDouble, ellipse::{seed, EllipseSolver}, hyperbola::HyperbolaSolver};
+
+/// Newton's method stops once a step is this small relative to the anomaly
+const STEP_THRESHOLD: f64 = 1.0e-32;
+
+fn newton(initial: f64, f: impl Fn(DoubleDouble) -> (DoubleDouble, DoubleDouble)) -> DoubleDouble {
+    let mut x = DoubleDouble::from(initial);
+    for _ in 0..MAX_ITERATIONS {
+        let (value, derivative) = f(x);
+        if value == DoubleDouble::ZERO {
+            break;
+        }
+        let step = value / derivative;
+        x = x - step;
+        if step.abs().hi <= STEP_THRESHOLD * x.abs().hi {
+            break;
+        }
+    }
+    x
+}
+
+/// Solves E - e sin(E) = M in double-double, returning the same continuous eccentric anomaly as
+/// `EllipseSolver::solve_unwrapped`
+pub fn solve_ellipse(eccentricity: f64, mean_anomaly: f64) -> DoubleDouble {
+    let initial = EllipseSolver::new(eccentricity).solve_unwrapped(mean_anomaly);
+    // The Laguerre step is 0/0 at e = 1, M = 0, where the seed is already the exact root
+    let initial = if initial.is_finite() { initial } else { seed(eccentricity, mean_anomaly) };
+    let (ec, mean_anomaly) = (DoubleDouble::from(eccentricity), DoubleDouble::from(mean_anomaly));
+    newton(initial, |eccentric_anomaly| {
+        let (sin, cos) = eccentric_anomaly.sin_cos();
+        (eccentric_anomaly - ec * sin - mean_anomaly, DoubleDouble::ONE - ec * cos)
+    })
+}
+
+/// Solves e sinh(F) - F = M in double-double
+pub fn solve_hyperbola(eccentricity: f64, mean_anomaly: f64) -> DoubleDouble {
+    let initial = HyperbolaSolver::new(eccentricity).solve(mean_anomaly);
+    let (ec, mean_anomaly) = (DoubleDouble::from(eccentricity), DoubleDouble::from(mean_anomaly));
+    newton(initial, |hyperbolic_anomaly| {
+        let (sinh, cosh) = hyperbolic_anomaly.sinh_cosh();
+        (ec * sinh - hyperbolic_anomaly - mean_anomaly, ec * cosh - DoubleDouble::ONE)
+    })
+}
+
+/// The error of `value` in units in the last place of the correctly rounded reference. An error
+/// of 0.5 or less means `value` is the correctly rounded result
+pub fn ulp_error(value: f64, reference: DoubleDouble) -> f64 {
+    let rounded = reference.to_f64().abs();
+    let ulp = if rounded == 0.0 { f64::from_bits(1) } else { f64::from_bits(rounded.to_bits() + 1) - rounded };
+    (DoubleDouble::from(value) - reference).to_f64().abs() / ulp
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use crate::{bisection::bisection, double_double::DoubleDouble, ellipse::EllipseSolver, hyperbola::HyperbolaSolver};
+
+    use super::{solve_ellipse, solve_hyperbola, ulp_error};
+
+    #[test]
+    fn test_reference_ellipse() {
+        for i in 0..=20 {
+            let e = i as f64 / 20.0;
+            for j in -200..200 {
+                let m = j as f64 / 20.0;
+                let reference = solve_ellipse(e, m);
+                // At e = 1 the root at M = 0 is triple, so bisection in f64 can't pin it down
+                if e < 1.0 {
+                    let expected = bisection(&|x: f64| x - e * x.sin() - m, -100.0, 100.0);
+                    assert!((reference.to_f64() - expected).abs() < 1.0e-12);
+                }
+                assert!(ulp_error(reference.to_f64(), reference) <= 0.5);
+
+                let (sin, _) = reference.sin_cos();
+                let residual = reference - DoubleDouble::from(e) * sin - m.into();
+                assert!(residual.abs().hi < 1.0e-30 * reference.abs().hi.max(1.0));
+
+                // The solver stops once the Laguerre delta falls below its threshold, so it's only
+                // that accurate; far from correctly rounded for small anomalies
+                let actual = EllipseSolver::new(e).solve_unwrapped(m);
+                if actual.is_finite() {
+                    assert!((DoubleDouble::from(actual) - reference).abs().hi < 1.0e-10);
+                }
+            }
+        }
+        assert_eq!(solve_ellipse(0.5, 0.0).to_f64(), 0.0);
+        assert_eq!(solve_ellipse(1.0, 0.0).to_f64(), 0.0);
+        assert!((solve_ellipse(0.0, 1000.0 * PI).to_f64() - 1000.0 * PI).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn test_reference_hyperbola() {
+        for e in [1.01, 1.1, 1.5, 2.0, 5.0, 20.0] {
+            for j in -200..200 {
+                let m = f64::powi(j as f64, 3) / 1000.0;
+                let reference = solve_hyperbola(e, m);
+                assert!(ulp_error(reference.to_f64(), reference) <= 0.5);
+
+                let (sinh, _) = reference.sinh_cosh();
+                let residual = DoubleDouble::from(e) * sinh - reference - m.into();
+                assert!(residual.abs().hi < 1.0e-30 * m.abs().max(1.0));
+
+                let actual = HyperbolaSolver::new(e).solve(m);
+                assert!((DoubleDouble::from(actual) - reference).abs().hi < 1.0e-10 * reference.abs().hi.max(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ulp_error() {
+        let reference = DoubleDouble::from(1.0) + DoubleDouble::from(f64::EPSILON / 4.0);
+        assert_eq!(ulp_error(1.0, reference), 0.25);
+        assert_eq!(ulp_error(1.0 + f64::EPSILON, reference), 0.75);
+        assert_eq!(ulp_error(0.0, DoubleDouble::ZERO), 0.0);
+    }
+}
@@ -0,0 +1,32 @@
+//! Diagnostics of a single solve, for tracking down where and why a solver misbehaves
+
+use serde::{Deserialize, Serialize};
+
+/// Which initial approximation the hyperbolic solver used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HyperbolaBranch {
+    /// The piecewise Pade approximation, with the index of the interval into the table of Pade
+    /// orders. Lower indices are further from periapsis
+    Pade { interval: usize },
+    /// The analytic approximation used for large mean anomalies
+    Analytic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SolveReport<T = f64> {
+    /// The same anomaly `solve` returns
+    pub anomaly: T,
+    /// Kepler's equation evaluated at the anomaly, E - e sin(E) - M or e sinh(F) - F - M. The
+    /// elliptic residual is taken with the mean anomaly reduced into [-pi, pi], so it isn't swamped
+    /// by rounding for mean anomalies many revolutions out
+    pub residual: T,
+    /// Steps of its `KeplerMethod` evaluated by the elliptic solver, including the last one whose
+    /// delta was small enough to stop on, plus any refinement steps, so one step and the refinement
+    /// steps for a non-iterative method. For the hyperbolic solver, Halley steps on the Pade cubic and on
+    /// Kepler's equation combined
+    pub iterations: usize,
+    /// Whether the iteration converged before hitting the iteration limit
+    pub converged: bool,
+    /// Which initial approximation the hyperbolic solver used; `None` for the elliptic solver
+    pub branch: Option<HyperbolaBranch>,
+}
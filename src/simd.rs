@@ -0,0 +1,581 @@
+//! Kernels that solve a fixed number of lanes at once with x86_64 vector instructions. The lanes
+//! are packed into AVX registers when the CPU supports it, which is checked at runtime, and into
+//! SSE2 registers otherwise, since every x86_64 CPU has those. The kernels are written once against
+//! the `Lanes` trait and instantiated for both.
+//!
+//! The iterations run on vectors, with polynomials in place of the library sin, cos, sinh and cosh.
+//! The range reduction, the table lookups, the logarithm in the hyperbolic starter and the elliptic
+//! refinement steps are the scalar ones, run on each lane. Where the scalar solvers loop until
+//! converged, lanes that have converged keep being computed but stop being updated, and the loop
+//! ends once every lane in the vector has converged.
+
+use std::{arch::x86_64::*, ops::{Add, Div, Mul, Sub}};
+
+use lazy_static::lazy_static;
+
+use crate::{config::SolverConfig, ellipse::{reduce_mean_anomaly, refinement_delta, wrap}, hyperbola::PADE_ORDERS};
+
+// Adding and subtracting 1.5 * 2^52 rounds to the nearest integer
+const ROUND_MAGIC: f64 = 6_755_399_441_055_744.0;
+
+// ln(2) split so that k ln(2) is exact in the high part, from fdlibm
+const LN_2_HI: f64 = 6.931_471_803_691_238e-1;
+const LN_2_LO: f64 = 1.908_214_929_270_587_7e-10;
+
+lazy_static! {
+    // Computed the same way as in pade_approximation, so that the coefficients come out the same
+    static ref PADE_ORDER_SINHS: [f64; 15] = PADE_ORDERS.map(|a| (a.exp() - (-a).exp()) / 2.0);
+    static ref PADE_ORDER_COSHS: [f64; 15] = PADE_ORDERS.map(|a| (a.exp() + (-a).exp()) / 2.0);
+}
+
+/// A vector of `WIDTH` f64 lanes. Comparisons return a mask of the same type, with every bit of a
+/// lane set where the comparison holds
+trait Lanes: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> {
+    const WIDTH: usize;
+
+    fn splat(x: f64) -> Self;
+    /// Reads the first `WIDTH` elements of `x`
+    fn load(x: &[f64]) -> Self;
+    /// Writes to the first `WIDTH` elements of `out`
+    fn store(self, out: &mut [f64]);
+    fn sqrt(self) -> Self;
+    fn and(self, other: Self) -> Self;
+    fn or(self, other: Self) -> Self;
+    fn xor(self, other: Self) -> Self;
+    fn lt(self, other: Self) -> Self;
+    fn le(self, other: Self) -> Self;
+    fn is_nan(self) -> Self;
+    /// NaN stays NaN
+    fn clamp(self, min: f64, max: f64) -> Self;
+    /// 2^`self`, for integers from -1022 to 1023
+    fn pow2(self) -> Self;
+    /// `a` in the lanes where `mask` is set and `b` in the rest. Each lane of `mask` has to be all
+    /// ones or all zeros
+    fn select(mask: Self, a: Self, b: Self) -> Self;
+    /// The top bit of each lane, lane 0 in bit 0
+    fn bits(self) -> u32;
+
+    #[inline(always)]
+    fn all(self) -> bool {
+        self.bits() == (1 << Self::WIDTH) - 1
+    }
+
+    #[inline(always)]
+    fn not(self) -> Self {
+        self.xor(Self::splat(f64::from_bits(u64::MAX)))
+    }
+
+    #[inline(always)]
+    fn neg(self) -> Self {
+        self.xor(Self::splat(-0.0))
+    }
+
+    #[inline(always)]
+    fn abs(self) -> Self {
+        self.and(Self::splat(f64::from_bits(!(-0.0f64).to_bits())))
+    }
+
+    /// The magnitude of `self` with the sign of `sign`
+    #[inline(always)]
+    fn copysign(self, sign: Self) -> Self {
+        self.abs().or(sign.and(Self::splat(-0.0)))
+    }
+}
+
+macro_rules! impl_operators {
+    ($lanes:ident, $add:ident, $sub:ident, $mul:ident, $div:ident) => {
+        impl Add for $lanes {
+            type Output = Self;
+            #[inline(always)]
+            fn add(self, other: Self) -> Self { Self(unsafe { $add(self.0, other.0) }) }
+        }
+
+        impl Sub for $lanes {
+            type Output = Self;
+            #[inline(always)]
+            fn sub(self, other: Self) -> Self { Self(unsafe { $sub(self.0, other.0) }) }
+        }
+
+        impl Mul for $lanes {
+            type Output = Self;
+            #[inline(always)]
+            fn mul(self, other: Self) -> Self { Self(unsafe { $mul(self.0, other.0) }) }
+        }
+
+        impl Div for $lanes {
+            type Output = Self;
+            #[inline(always)]
+            fn div(self, other: Self) -> Self { Self(unsafe { $div(self.0, other.0) }) }
+        }
+    };
+}
+
+/// Two lanes in an SSE2 register. SSE2 is part of x86_64, so these can be used anywhere
+#[derive(Clone, Copy)]
+struct Sse2(__m128d);
+
+impl_operators!(Sse2, _mm_add_pd, _mm_sub_pd, _mm_mul_pd, _mm_div_pd);
+
+impl Lanes for Sse2 {
+    const WIDTH: usize = 2;
+
+    #[inline(always)]
+    fn splat(x: f64) -> Self { Self(unsafe { _mm_set1_pd(x) }) }
+    #[inline(always)]
+    fn load(x: &[f64]) -> Self { Self(unsafe { _mm_loadu_pd(x[..2].as_ptr()) }) }
+    #[inline(always)]
+    fn store(self, out: &mut [f64]) { unsafe { _mm_storeu_pd(out[..2].as_mut_ptr(), self.0) } }
+    #[inline(always)]
+    fn sqrt(self) -> Self { Self(unsafe { _mm_sqrt_pd(self.0) }) }
+    #[inline(always)]
+    fn and(self, other: Self) -> Self { Self(unsafe { _mm_and_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn or(self, other: Self) -> Self { Self(unsafe { _mm_or_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self { Self(unsafe { _mm_xor_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn lt(self, other: Self) -> Self { Self(unsafe { _mm_cmplt_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn le(self, other: Self) -> Self { Self(unsafe { _mm_cmple_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn is_nan(self) -> Self { Self(unsafe { _mm_cmpunord_pd(self.0, self.0) }) }
+    #[inline(always)]
+    fn clamp(self, min: f64, max: f64) -> Self {
+        // min and max return their second operand if either is NaN
+        Self(unsafe { _mm_min_pd(_mm_set1_pd(max), _mm_max_pd(_mm_set1_pd(min), self.0)) })
+    }
+    #[inline(always)]
+    fn pow2(self) -> Self {
+        // Adding 2^52 puts the biased exponent in the low bits of the mantissa, and the shift moves
+        // it up into the exponent field
+        let biased = self + Self::splat(4_503_599_627_371_519.0);
+        Self(unsafe { _mm_castsi128_pd(_mm_slli_epi64::<52>(_mm_castpd_si128(biased.0))) })
+    }
+    #[inline(always)]
+    fn select(mask: Self, a: Self, b: Self) -> Self {
+        // There's no blend before SSE4.1
+        Self(unsafe { _mm_or_pd(_mm_and_pd(mask.0, a.0), _mm_andnot_pd(mask.0, b.0)) })
+    }
+    #[inline(always)]
+    fn bits(self) -> u32 { unsafe { _mm_movemask_pd(self.0) as u32 } }
+}
+
+/// Four lanes in an AVX register. These are only created inside functions compiled with AVX
+/// enabled, which are only called once the CPU is known to support it, so the intrinsics in their
+/// methods never run on a CPU without AVX
+#[derive(Clone, Copy)]
+struct Avx(__m256d);
+
+impl_operators!(Avx, _mm256_add_pd, _mm256_sub_pd, _mm256_mul_pd, _mm256_div_pd);
+
+impl Lanes for Avx {
+    const WIDTH: usize = 4;
+
+    #[inline(always)]
+    fn splat(x: f64) -> Self { Self(unsafe { _mm256_set1_pd(x) }) }
+    #[inline(always)]
+    fn load(x: &[f64]) -> Self { Self(unsafe { _mm256_loadu_pd(x[..4].as_ptr()) }) }
+    #[inline(always)]
+    fn store(self, out: &mut [f64]) { unsafe { _mm256_storeu_pd(out[..4].as_mut_ptr(), self.0) } }
+    #[inline(always)]
+    fn sqrt(self) -> Self { Self(unsafe { _mm256_sqrt_pd(self.0) }) }
+    #[inline(always)]
+    fn and(self, other: Self) -> Self { Self(unsafe { _mm256_and_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn or(self, other: Self) -> Self { Self(unsafe { _mm256_or_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self { Self(unsafe { _mm256_xor_pd(self.0, other.0) }) }
+    #[inline(always)]
+    fn lt(self, other: Self) -> Self { Self(unsafe { _mm256_cmp_pd::<_CMP_LT_OQ>(self.0, other.0) }) }
+    #[inline(always)]
+    fn le(self, other: Self) -> Self { Self(unsafe { _mm256_cmp_pd::<_CMP_LE_OQ>(self.0, other.0) }) }
+    #[inline(always)]
+    fn is_nan(self) -> Self { Self(unsafe { _mm256_cmp_pd::<_CMP_UNORD_Q>(self.0, self.0) }) }
+    #[inline(always)]
+    fn clamp(self, min: f64, max: f64) -> Self {
+        Self(unsafe { _mm256_min_pd(_mm256_set1_pd(max), _mm256_max_pd(_mm256_set1_pd(min), self.0)) })
+    }
+    #[inline(always)]
+    fn pow2(self) -> Self {
+        // AVX has no 256 bit integer shifts, so each half goes through SSE2
+        unsafe {
+            let low = Sse2(_mm256_castpd256_pd128(self.0)).pow2();
+            let high = Sse2(_mm256_extractf128_pd::<1>(self.0)).pow2();
+            Self(_mm256_insertf128_pd::<1>(_mm256_castpd128_pd256(low.0), high.0))
+        }
+    }
+    #[inline(always)]
+    fn select(mask: Self, a: Self, b: Self) -> Self { Self(unsafe { _mm256_blendv_pd(b.0, a.0, mask.0) }) }
+    #[inline(always)]
+    fn bits(self) -> u32 { unsafe { _mm256_movemask_pd(self.0) as u32 } }
+}
+
+/// Taylor series for sin and cos, accurate to a few ulp for |x| <= pi/2 + 0.1
+#[inline(always)]
+fn sin_cos_polynomial<V: Lanes>(x: V) -> (V, V) {
+    let x2 = x * x;
+    let one = V::splat(1.0);
+    let mut sin = one;
+    let mut cos = one;
+    // Horner's scheme from the x^23 and x^22 terms down. The reciprocals are folded into constants,
+    // since dividing is much slower than multiplying
+    for n in (1..=11).rev() {
+        let n = n as f64;
+        sin = one - x2 * V::splat(1.0 / ((2.0*n) * (2.0*n + 1.0))) * sin;
+        cos = one - x2 * V::splat(1.0 / ((2.0*n - 1.0) * (2.0*n))) * cos;
+    }
+    (x * sin, cos)
+}
+
+/// Works for -pi/2 <= x <= 3pi/2, which covers every iterate of the elliptic solver
+#[inline(always)]
+fn sin_cos<V: Lanes>(x: V) -> (V, V) {
+    // sin(pi - x) = sin(x) and cos(pi - x) = -cos(x)
+    let reflect = V::splat(std::f64::consts::FRAC_PI_2).lt(x);
+    let (sin, cos) = sin_cos_polynomial(V::select(reflect, V::splat(std::f64::consts::PI) - x, x));
+    (sin, cos.xor(reflect.and(V::splat(-0.0))))
+}
+
+/// Rounds to the nearest integer for |x| < 2^51
+#[inline(always)]
+fn round<V: Lanes>(x: V) -> V {
+    (x + V::splat(ROUND_MAGIC)) - V::splat(ROUND_MAGIC)
+}
+
+/// Overflows to infinity and underflows to zero like the library exp
+#[inline(always)]
+fn exp<V: Lanes>(x: V) -> V {
+    let x = x.clamp(-1400.0, 1400.0);
+    let k = round(x * V::splat(std::f64::consts::LOG2_E));
+    let r = x - k * V::splat(LN_2_HI) - k * V::splat(LN_2_LO);
+    let one = V::splat(1.0);
+    let mut series = one;
+    for n in (1..=13).rev() {
+        series = one + r * V::splat(1.0 / n as f64) * series;
+    }
+    // 2^k is built as two factors so that neither exponent field overflows
+    let k1 = round(V::splat(0.5) * k);
+    series * k1.pow2() * (k - k1).pow2()
+}
+
+#[inline(always)]
+fn sinh_cosh<V: Lanes>(x: V) -> (V, V) {
+    let ex = exp(x.abs());
+    let enx = V::splat(1.0) / ex;
+    // (e^x - e^-x) / 2 cancels catastrophically for small x, so use the series there
+    let x2 = x * x;
+    let one = V::splat(1.0);
+    let mut series = one;
+    for n in (1..=9).rev() {
+        let n = n as f64;
+        series = one + x2 * V::splat(1.0 / ((2.0*n) * (2.0*n + 1.0))) * series;
+    }
+    let sinh = V::select(x.abs().lt(one), x * series, (V::splat(0.5) * (ex - enx)).copysign(x));
+    (sinh, V::splat(0.5) * (ex + enx))
+}
+
+#[inline(always)]
+fn two_sum<V: Lanes>(a: V, b: V) -> (V, V) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+/// Dekker's product, since there's no fused multiply-add on AVX alone. Splitting overflows for
+/// factors past about 2^996, where the error is far too small to matter, so it's dropped there
+#[inline(always)]
+fn two_product<V: Lanes>(a: V, b: V) -> (V, V) {
+    let split = |x: V| {
+        let t = V::splat(134_217_729.0) * x;
+        let high = t - (t - x);
+        (high, x - high)
+    };
+    let p = a * b;
+    let (a_high, a_low) = split(a);
+    let (b_high, b_low) = split(b);
+    let error = ((a_high * b_high - p) + a_high * b_low + a_low * b_high) + a_low * b_low;
+    (p, V::select(error.is_nan(), V::splat(0.0), error))
+}
+
+/// Same as the scalar `hyperbolic_kepler_residual`, with both branches computed
+#[inline(always)]
+fn hyperbolic_kepler_residual<V: Lanes>(ec: f64, mh: V, hyperbolic_anomaly: V, sinh_hyperbolic_anomaly: V) -> V {
+    let c = V::splat;
+    let x2 = hyperbolic_anomaly * hyperbolic_anomaly;
+    let mut series = c(1.0);
+    for n in (2..=9).rev() {
+        series = c(1.0) + x2 * c(1.0 / (2 * n * (2 * n + 1)) as f64) * series;
+    }
+    let cubic = x2 * hyperbolic_anomaly * c(1.0 / 6.0) * series;
+    let (ec_minus_one, ec_minus_one_error) = crate::double_double::two_sum(ec, -1.0);
+    let (linear, linear_error) = two_product(c(ec_minus_one), hyperbolic_anomaly);
+    let (cubic, cubic_error) = two_product(c(ec), cubic);
+    let (sum, sum_error) = two_sum(linear, cubic);
+    let (residual, residual_error) = two_sum(sum, mh.neg());
+    let near = residual + (c(ec_minus_one_error) * hyperbolic_anomaly + linear_error + cubic_error + sum_error + residual_error);
+
+    let (product, product_error) = two_product(c(ec), sinh_hyperbolic_anomaly);
+    let (difference, difference_error) = two_sum(product, hyperbolic_anomaly.neg());
+    let (residual, residual_error) = two_sum(difference, mh.neg());
+    let far = residual + (product_error + difference_error + residual_error);
+
+    V::select(hyperbolic_anomaly.abs().lt(c(1.0)), near, far)
+}
+
+/// Same as the scalar `halley_delta`
+#[inline(always)]
+fn halley_delta<V: Lanes>(ec: f64, mh: V, f0: V, sinh_f0: V, cosh_f0: V) -> V {
+    let c = V::splat;
+    let f = hyperbolic_kepler_residual(ec, mh, f0, sinh_f0);
+    let f_prime = c(ec) * cosh_f0 - c(1.0);
+    let f_prime_prime = c(ec) * sinh_f0;
+    (c(2.0) * f / f_prime) / (c(2.0) - f * f_prime_prime / (f_prime * f_prime))
+}
+
+/// Same test as `SolverConfig::converged`
+#[inline(always)]
+fn converged<V: Lanes>(config: &SolverConfig, delta: V, estimate: V) -> V {
+    delta.abs().lt(V::splat(config.absolute_tolerance) + V::splat(config.relative_tolerance) * estimate.abs())
+}
+
+pub(crate) fn solve_ellipse<const N: usize>(ec: f64, mean_anomalies: [f64; N], config: &SolverConfig) -> [f64; N] {
+    if is_x86_feature_detected!("avx") {
+        // SAFETY: the CPU supports AVX
+        unsafe { solve_ellipse_avx(ec, mean_anomalies, config) }
+    } else {
+        solve_ellipse_lanes::<Sse2, N>(ec, mean_anomalies, config)
+    }
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn solve_ellipse_avx<const N: usize>(ec: f64, mean_anomalies: [f64; N], config: &SolverConfig) -> [f64; N] {
+    solve_ellipse_lanes::<Avx, N>(ec, mean_anomalies, config)
+}
+
+#[inline(always)]
+fn solve_ellipse_lanes<V: Lanes, const N: usize>(ec: f64, mean_anomalies: [f64; N], config: &SolverConfig) -> [f64; N] {
+    // The scalar reduction, so that both see exactly the same reduced mean anomaly however many
+    // revolutions out it is
+    let signed_mean_anomalies = mean_anomalies.map(|mean_anomaly| reduce_mean_anomaly(mean_anomaly).0);
+    let reduced_mean_anomalies = signed_mean_anomalies.map(f64::abs);
+    let mut eccentric_anomalies = [0.0; N];
+    let mut done = [false; N];
+
+    for start in (0..N).step_by(V::WIDTH) {
+        let mean_anomaly = V::load(&reduced_mean_anomalies[start..]);
+        // Same as the scalar seed, operation for operation
+        let pi = std::f64::consts::PI;
+        let mut eccentric_anomaly = mean_anomaly
+            + V::splat(0.999_999 * 4.0 * ec) * mean_anomaly * (V::splat(pi) - mean_anomaly)
+            / (V::splat(8.0 * ec) * mean_anomaly + V::splat(4.0 * ec * (ec - pi)) + V::splat(pi * pi));
+
+        let mut converged_lanes = V::splat(0.0);
+        for _ in 0..config.max_iterations {
+            let (sin, cos) = sin_cos(eccentric_anomaly);
+            // Same as laguerre_step with N = 2
+            let f = mean_anomaly - eccentric_anomaly + V::splat(ec) * sin;
+            let f_prime = V::splat(-1.0) + V::splat(ec) * cos;
+            let f_prime_prime = V::splat(-ec) * sin;
+            let a = f_prime * f_prime - V::splat(2.0) * f * f_prime_prime;
+            let b = a.abs().sqrt().copysign(f_prime);
+            let delta = (V::splat(2.0) * f / (f_prime + b)).neg();
+
+            converged_lanes = converged_lanes.or(converged(config, delta, eccentric_anomaly));
+            eccentric_anomaly = eccentric_anomaly + V::select(converged_lanes, V::splat(0.0), delta);
+            if converged_lanes.all() {
+                break;
+            }
+        }
+
+        eccentric_anomaly.store(&mut eccentric_anomalies[start..]);
+        let bits = converged_lanes.bits();
+        for (lane, done) in done[start..start + V::WIDTH].iter_mut().enumerate() {
+            *done = bits & (1 << lane) != 0;
+        }
+    }
+
+    // Lanes that didn't converge aren't refined, like in the scalar solver
+    for i in 0..N {
+        let mut eccentric_anomaly = eccentric_anomalies[i];
+        if done[i] {
+            for _ in 0..config.refinement_steps {
+                let (sin, cos) = (eccentric_anomaly.sin(), eccentric_anomaly.cos());
+                eccentric_anomaly += refinement_delta(ec, reduced_mean_anomalies[i], eccentric_anomaly, sin, cos);
+            }
+        }
+        eccentric_anomalies[i] = wrap(eccentric_anomaly * signed_mean_anomalies[i].signum());
+    }
+    eccentric_anomalies
+}
+
+/// `pade_mean_anomaly_thresholds` are the thresholds stored in `HyperbolaSolver`
+pub(crate) fn solve_hyperbola<const N: usize>(ec: f64, pade_mean_anomaly_thresholds: &[f64; 15], mean_anomalies: [f64; N], config: &SolverConfig) -> [f64; N] {
+    if is_x86_feature_detected!("avx") {
+        // SAFETY: the CPU supports AVX
+        unsafe { solve_hyperbola_avx(ec, pade_mean_anomaly_thresholds, mean_anomalies, config) }
+    } else {
+        solve_hyperbola_lanes::<Sse2, N>(ec, pade_mean_anomaly_thresholds, mean_anomalies, config)
+    }
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn solve_hyperbola_avx<const N: usize>(ec: f64, pade_mean_anomaly_thresholds: &[f64; 15], mean_anomalies: [f64; N], config: &SolverConfig) -> [f64; N] {
+    solve_hyperbola_lanes::<Avx, N>(ec, pade_mean_anomaly_thresholds, mean_anomalies, config)
+}
+
+#[inline(always)]
+fn solve_hyperbola_lanes<V: Lanes, const N: usize>(ec: f64, pade_mean_anomaly_thresholds: &[f64; 15], mean_anomalies: [f64; N], config: &SolverConfig) -> [f64; N] {
+    let mhs = mean_anomalies.map(f64::abs);
+    let mut orders = [0.0; N];
+    let mut sinhs = [0.0; N];
+    let mut coshs = [0.0; N];
+    let mut logs = [0.0; N];
+
+    // Each lane only looks up what its own branch of the starter needs
+    for i in 0..N {
+        let mh = mhs[i];
+        if mh <= pade_mean_anomaly_thresholds[0] {
+            let mut interval = 0;
+            while interval < pade_mean_anomaly_thresholds.len() - 1 && mh < pade_mean_anomaly_thresholds[interval + 1] {
+                interval += 1;
+            }
+            orders[i] = PADE_ORDERS[interval];
+            sinhs[i] = PADE_ORDER_SINHS[interval];
+            coshs[i] = PADE_ORDER_COSHS[interval];
+        } else {
+            logs[i] = (2.0 * mh / ec).ln();
+        }
+    }
+
+    let mut hyperbolic_anomalies = [0.0; N];
+    for start in (0..N).step_by(V::WIDTH) {
+        let c = V::splat;
+        let mh = V::load(&mhs[start..]);
+        let pade = mh.le(c(pade_mean_anomaly_thresholds[0]));
+
+        // Same as pade_coefficients, operation for operation
+        let (a, sa, ca) = (V::load(&orders[start..]), V::load(&sinhs[start..]), V::load(&coshs[start..]));
+        let d1 = ca * ca + c(3.0);
+        let d2 = sa * sa + c(4.0);
+        let p1 = ca * (c(3.0) * (ca * ca) + c(17.0)) / (c(5.0) * d1);
+        let p2 = sa * (c(3.0) * (sa * sa) + c(28.0)) / (c(20.0) * d2);
+        let p3 = ca * (ca * ca + c(27.0)) / (c(60.0) * d1);
+        let q1 = c(-2.0) * ca * sa / (c(5.0) * d1);
+        let q2 = (sa * sa - c(4.0)) / (c(20.0) * d2);
+        let c3 = c(ec) * p3 - q2;
+        let c2 = c(ec) * p2 - (mh + a) * q2 - q1;
+        let c1 = c(ec) * p1 - (mh + a) * q1 - c(1.0);
+        let c0 = c(ec) * sa - mh - a;
+
+        // Same as solve_cubic. Lanes that take the analytic branch start out converged
+        let mut x = mh / c(ec - 1.0);
+        let mut converged_lanes = pade.not();
+        for _ in 0..config.max_iterations {
+            let f = ((c3 * x + c2) * x + c1) * x + c0;
+            let f_prime = (c(3.0) * c3 * x + c(2.0) * c2) * x + c1;
+            let f_prime_prime = c(6.0) * c3 * x + c(2.0) * c2;
+            let delta = c(-2.0) * f * f_prime / (c(2.0) * (f_prime * f_prime) - f * f_prime_prime);
+            converged_lanes = converged_lanes.or(converged(config, delta, x));
+            x = x + V::select(converged_lanes, c(0.0), delta);
+            if converged_lanes.all() {
+                break;
+            }
+        }
+
+        // Same as the large mean anomaly branch of the scalar starter
+        let fa = V::load(&logs[start..]);
+        let ca = c(0.5) * (c(2.0) * mh / c(ec) + c(ec) / (c(2.0) * mh));
+        let sa = c(0.5) * (c(2.0) * mh / c(ec) - c(ec) / (c(2.0) * mh));
+        let denominator = c(ec) * ca - c(1.0);
+        let numerator = c(ec * ec) / (c(4.0) * mh) + fa;
+        let ratio = numerator / denominator;
+        let top = c(6.0) * numerator / denominator + c(3.0) * (c(ec) * sa / denominator) * (ratio * ratio);
+        let bottom = c(6.0) + c(6.0) * (c(ec) * sa / denominator) * ratio + (c(ec) * ca / denominator) * (ratio * ratio);
+        let analytic = fa + top / bottom;
+
+        let mut hyperbolic_anomaly = V::select(pade, x + a, analytic);
+        for _ in 0..config.refinement_steps {
+            let (sinh, cosh) = sinh_cosh(hyperbolic_anomaly);
+            hyperbolic_anomaly = hyperbolic_anomaly - halley_delta(ec, mh, hyperbolic_anomaly, sinh, cosh);
+        }
+        hyperbolic_anomaly.store(&mut hyperbolic_anomalies[start..]);
+    }
+
+    for i in 0..N {
+        hyperbolic_anomalies[i] *= mean_anomalies[i].signum();
+    }
+    hyperbolic_anomalies
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{config::{Precision, SolverConfig}, ellipse::EllipseSolver, hyperbola::HyperbolaSolver};
+
+    use super::{exp, sin_cos, sinh_cosh, solve_ellipse_lanes, solve_hyperbola_lanes, Avx, Lanes, Sse2};
+
+    fn check_elementary_functions<V: Lanes>() {
+        let mut first = [0.0; 4];
+        let mut second = [0.0; 4];
+        for x in (-1000..=3000).step_by(V::WIDTH) {
+            let xs: [f64; 4] = std::array::from_fn(|i| (x + i as i32) as f64 / 1000.0 * 1.6);
+            let (sin, cos) = sin_cos(V::load(&xs));
+            sin.store(&mut first);
+            cos.store(&mut second);
+            for i in 0..V::WIDTH {
+                assert!((first[i] - xs[i].sin()).abs() < 1.0e-15 && (second[i] - xs[i].cos()).abs() < 1.0e-15, "{}", xs[i]);
+            }
+        }
+        for x in (-7000..=7000).step_by(V::WIDTH) {
+            let xs: [f64; 4] = std::array::from_fn(|i| (x + i as i32) as f64 / 10.0);
+            exp(V::load(&xs)).store(&mut first);
+            for i in 0..V::WIDTH {
+                assert!((first[i] / xs[i].exp() - 1.0).abs() < 1.0e-15, "{}", xs[i]);
+            }
+            let xs = xs.map(|x| x / 10.0);
+            let (sinh, cosh) = sinh_cosh(V::load(&xs));
+            sinh.store(&mut first);
+            cosh.store(&mut second);
+            for i in 0..V::WIDTH {
+                assert!((first[i] - xs[i].sinh()).abs() <= 1.0e-15 * xs[i].sinh().abs(), "{}", xs[i]);
+                assert!((second[i] / xs[i].cosh() - 1.0).abs() < 1.0e-15, "{}", xs[i]);
+            }
+        }
+        exp(V::load(&[1000.0, -1000.0, 0.0, 0.0])).store(&mut first);
+        exp(V::splat(f64::NAN)).store(&mut second);
+        assert!(first[0] == f64::INFINITY && first[1] == 0.0 && second[0].is_nan());
+    }
+
+    #[test]
+    fn test_simd_elementary_functions() {
+        check_elementary_functions::<Sse2>();
+        if is_x86_feature_detected!("avx") {
+            #[target_feature(enable = "avx")]
+            unsafe fn check_avx() {
+                check_elementary_functions::<Avx>();
+            }
+            // SAFETY: the CPU supports AVX
+            unsafe { check_avx() }
+        }
+    }
+
+    #[test]
+    fn test_simd_sse2_lanes() {
+        // solve_x8 runs the AVX kernel where the CPU has it, and both should do exactly the same
+        // operations on each lane
+        for precision in [Precision::Balanced, Precision::Exact] {
+            for x in -100..100 {
+                let mean_anomalies: [f64; 8] = std::array::from_fn(|i| f64::powi((8 * x + i as i32) as f64, 3) / 1.0e4);
+                for e in [0.0, 0.3, 0.9, 0.999] {
+                    let config = SolverConfig::ellipse(precision);
+                    let expected = EllipseSolver::with_config(e, config).solve_x8(mean_anomalies);
+                    assert_eq!(solve_ellipse_lanes::<Sse2, 8>(e, mean_anomalies, &config), expected);
+                }
+                for e in [1.001, 1.3, 2.0, 100.0] {
+                    let solver = HyperbolaSolver::with_config(e, SolverConfig::hyperbola(precision));
+                    let actual = solve_hyperbola_lanes::<Sse2, 8>(e, &solver.pade_mean_anomaly_thresholds, mean_anomalies, &solver.config);
+                    assert_eq!(actual, solver.solve_x8(mean_anomalies));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{Precision, SolverConfig}, ellipse::laguerre_delta, error::SolveError, float::Float};
+
+// Below this the closed forms of the Stumpff functions lose precision to cancellation
+const STUMPFF_SERIES_THRESHOLD: f64 = 1.0;
+const STUMPFF_SERIES_TERMS: usize = 10;
+
+/// The Stumpff function c2(z) = (1 - cos(sqrt(z))) / z, continued analytically to z <= 0
+pub fn stumpff_c2<T: Float>(z: T) -> T {
+    let c = T::from_f64;
+    if z.abs() < c(STUMPFF_SERIES_THRESHOLD) {
+        // c2(z) = sum of (-z)^k / (2k + 2)!
+        let mut term = c(0.5);
+        let mut sum = term;
+        for k in 1..STUMPFF_SERIES_TERMS {
+            let k = k as f64;
+            term *= -z / c((2.0 * k + 1.0) * (2.0 * k + 2.0));
+            sum += term;
+        }
+        sum
+    } else if z > c(0.0) {
+        (c(1.0) - T::cos(z.sqrt())) / z
+    } else {
+        (T::cosh((-z).sqrt()) - c(1.0)) / -z
+    }
+}
+
+/// The Stumpff function c3(z) = (sqrt(z) - sin(sqrt(z))) / z^(3/2), continued analytically to z <= 0
+pub fn stumpff_c3<T: Float>(z: T) -> T {
+    let c = T::from_f64;
+    if z.abs() < c(STUMPFF_SERIES_THRESHOLD) {
+        // c3(z) = sum of (-z)^k / (2k + 3)!
+        let mut term = c(1.0 / 6.0);
+        let mut sum = term;
+        for k in 1..STUMPFF_SERIES_TERMS {
+            let k = k as f64;
+            term *= -z / c((2.0 * k + 2.0) * (2.0 * k + 3.0));
+            sum += term;
+        }
+        sum
+    } else if z > c(0.0) {
+        let sqrt_z = z.sqrt();
+        (sqrt_z - sqrt_z.sin()) / (z * sqrt_z)
+    } else {
+        let sqrt_z = (-z).sqrt();
+        (sqrt_z.sinh() - sqrt_z) / (-z * sqrt_z)
+    }
+}
+
+/// Solves the universal Kepler equation with time measured from periapsis,
+/// M = x + e x^3 c3((1 - e) x^2), where M = sqrt(mu / q^3) * (t - T) and x is the universal
+/// anomaly normalised by sqrt(q). Unlike `EllipseSolver` and `HyperbolaSolver`, nothing here
+/// divides by (1 - e), so it stays accurate as the eccentricity crosses 1.
+///
+/// The universal anomaly converts to the other anomalies as E = x sqrt(1 - e) for ellipses,
+/// D = x / sqrt(2) for parabolas and F = x sqrt(e - 1) for hyperbolas.
+/// ## Example
+/// ```rs
+/// use rust_kepler_solver::universal::UniversalSolver;
+///
+/// fn example_universal() {
+///     let eccentricity = 0.999;
+///     let solver = UniversalSolver::new(eccentricity);
+///     println!("{}", solver.solve(1.2));
+///     println!("{}", solver.solve(100.0));
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UniversalSolver<T = f64> {
+    eccentricity: T,
+    config: SolverConfig<T>,
+}
+
+impl<T: Float> UniversalSolver<T> {
+    pub fn new(eccentricity: T) -> Self {
+        Self::with_config(eccentricity, SolverConfig::universal(Precision::Balanced))
+    }
+
+    pub fn with_config(eccentricity: T, config: SolverConfig<T>) -> Self {
+        Self { eccentricity, config }
+    }
+
+    /// Designed for 0.9 < eccentricity < 1.1, but works for all eccentricities above 0, and all
+    /// values of mean anomaly. Always terminates
+    pub fn solve(&self, mean_anomaly: T) -> T {
+        self.iterate(mean_anomaly).0
+    }
+
+    /// Returns an error rather than a garbage value if the input is invalid or the iteration fails
+    /// to converge
+    pub fn try_solve(&self, mean_anomaly: T) -> Result<T, SolveError> {
+        if !mean_anomaly.is_finite() || !self.eccentricity.is_finite() {
+            return Err(SolveError::NonFiniteInput);
+        }
+        if self.eccentricity <= T::from_f64(0.0) {
+            return Err(SolveError::EccentricityOutOfRange { eccentricity: self.eccentricity.to_f64() });
+        }
+        match self.iterate(mean_anomaly) {
+            (universal_anomaly, true) => Ok(universal_anomaly),
+            (_, false) => Err(SolveError::NotConverged { iterations: self.config.max_iterations }),
+        }
+    }
+
+    /// Returns the universal anomaly and whether the iteration converged
+    fn iterate(&self, mean_anomaly: T) -> (T, bool) {
+        // The equation is odd, so solve for |M| and flip the sign of the output
+        let c = T::from_f64;
+        let ec = self.eccentricity;
+        let alpha = c(1.0) - ec;
+        let mu = mean_anomaly.abs();
+
+        // Start from the root of the parabolic approximation, x + e x^3 / 6 = M (c3(0) = 1/6),
+        // using the same sinh substitution as the parabola solver
+        let mut x = c(2.0) * T::sqrt(c(2.0) / ec) * T::sinh(T::asinh(c(1.5) * mu * T::sqrt(ec / c(2.0))) / c(3.0));
+        let z = alpha * x.powi(2);
+        if z > c(1.0) {
+            // Far from periapsis on an ellipse, so the parabolic seed is poor; use Danby's seed instead
+            let me = mu * alpha.powf(c(1.5));
+            x = (me + c(0.85) * ec * me.sin().signum()) / alpha.sqrt();
+        } else if z < c(-1.0) {
+            // Far from periapsis on a hyperbola, sinh(F) ~ (M + F) / e gives a lower bound that
+            // gets tighter as M grows
+            let mh = mu * (-alpha).powf(c(1.5));
+            let lower_bound = T::asinh(mh / ec);
+            x = T::asinh((mh + lower_bound) / ec) / (-alpha).sqrt();
+        }
+
+        let mut converged = false;
+        let mut refinement_steps = self.config.refinement_steps;
+        for _ in 0..self.config.max_iterations + self.config.refinement_steps {
+            let z = alpha * x.powi(2);
+            let c2 = stumpff_c2(z);
+            let c3 = stumpff_c3(z);
+            let c1 = c(1.0) - z * c3;
+            let f = x + ec * x.powi(3) * c3 - mu;
+            let f_prime = c(1.0) + ec * x.powi(2) * c2;
+            let f_prime_prime = ec * x * c1;
+            let delta = laguerre_delta(f, f_prime, f_prime_prime);
+            x += delta;
+            if self.config.converged(delta, x) {
+                if refinement_steps == 0 {
+                    converged = true;
+                    break;
+                }
+                refinement_steps -= 1;
+            }
+        }
+
+        (x * mean_anomaly.signum(), converged)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{bisection::bisection, config::{Precision, SolverConfig}, error::SolveError, parabola::ParabolaSolver};
+
+    use super::UniversalSolver;
+
+    fn solve_with_bisection(e: f64, m: f64) -> f64 {
+        // Bisect the classical equation and convert, so the test doesn't depend on the Stumpff functions
+        if e < 1.0 {
+            let m = m * (1.0 - e).powf(1.5);
+            let f = |eccentric_anomaly: f64| eccentric_anomaly - e*eccentric_anomaly.sin() - m;
+            bisection(&f, m - 1.0, m + 1.0) / (1.0 - e).sqrt()
+        } else {
+            let m = m * (e - 1.0).powf(1.5);
+            let f = |eccentric_anomaly: f64| e * f64::sinh(eccentric_anomaly) - eccentric_anomaly - m;
+            bisection(&f, -1.0, f64::asinh(m / (e - 1.0)) + 1.0) / (e - 1.0).sqrt()
+        }
+    }
+
+    #[test]
+    fn test_universal() {
+        let eccentricites: Vec<f64> = (-100..100)
+            .filter(|x| *x != 0)
+            .map(|x| 1.0 + x as f64 / 1000.0)
+            .collect();
+        let mean_anomalies: Vec<f64> = (0..1000)
+            .map(|x| f64::powi(x as f64, 2) / 1000.0)
+            .collect();
+
+        for e in &eccentricites {
+            let solver = UniversalSolver::new(*e);
+            for m in &mean_anomalies {
+                let expected = solve_with_bisection(*e, *m);
+                let actual = solver.solve(*m);
+                let difference = if actual.abs() < 1.0e-5 { expected - actual } else { (expected - actual) / actual }.abs();
+                if difference > 1.0e-10 {
+                    dbg!(expected, actual, difference, e, m);
+                    panic!()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_universal_parabola() {
+        let solver = UniversalSolver::new(1.0);
+        let parabola_solver = ParabolaSolver::new();
+        for x in -1000..1000 {
+            let m = f64::powi(x as f64, 3) / 1.0e5;
+            let expected = parabola_solver.solve(m / f64::sqrt(2.0)) * f64::sqrt(2.0);
+            let actual = solver.solve(m);
+            let difference = if actual.abs() < 1.0e-5 { expected - actual } else { (expected - actual) / actual }.abs();
+            if difference > 1.0e-10 {
+                dbg!(expected, actual, difference, m);
+                panic!()
+            }
+        }
+    }
+
+    #[test]
+    fn test_universal_symmetry() {
+        let solver = UniversalSolver::new(0.95);
+        for x in 0..1000 {
+            let m = x as f64 / 10.0;
+            assert_eq!(solver.solve(m), -solver.solve(-m));
+        }
+    }
+
+    #[test]
+    fn test_universal_try_solve() {
+        let solver = UniversalSolver::new(1.05);
+        assert_eq!(solver.try_solve(1.0), Ok(solver.solve(1.0)));
+        assert_eq!(solver.try_solve(f64::NAN), Err(SolveError::NonFiniteInput));
+        assert_eq!(UniversalSolver::new(0.0).try_solve(1.0), Err(SolveError::EccentricityOutOfRange { eccentricity: 0.0 }));
+        assert!(UniversalSolver::new(0.95).solve(f64::INFINITY).is_nan());
+    }
+
+    #[test]
+    fn test_universal_config() {
+        for e in [0.95, 0.999, 1.0, 1.05] {
+            let fast = UniversalSolver::with_config(e, SolverConfig::universal(Precision::Fast));
+            let exact = UniversalSolver::with_config(e, SolverConfig::universal(Precision::Exact));
+            for x in 0..100 {
+                let m = f64::powi(x as f64, 2) / 10.0;
+                let expected = UniversalSolver::new(e).solve(m);
+                assert!((fast.solve(m) - expected).abs() < 1.0e-4 * expected.abs().max(1.0));
+                assert!((exact.solve(m) - expected).abs() < 1.0e-12 * expected.abs().max(1.0));
+            }
+        }
+        let config = SolverConfig { max_iterations: 1, ..SolverConfig::universal(Precision::Balanced) };
+        assert_eq!(UniversalSolver::with_config(0.95, config).try_solve(50.0), Err(SolveError::NotConverged { iterations: 1 }));
+    }
+}